@@ -2,13 +2,113 @@
 #include "dualsense.h"
 #include <bpf/bpf_tracing.h>
 
+// Flags of the stick LUT values (Must match the ones in 'input/stick.rs')
+#define STICK_LUT_DEADZONE (1 << 16)
+#define STICK_LUT_MARGIN (1 << 17)
+#define STICK_LUT_DIRECTIONS_SHIFT 18 // Up, right, down and left past the button threshold
+#define STICK_CENTER 127
+
 struct stick_lut {
     __uint(type, BPF_MAP_TYPE_ARRAY);
     __uint(max_entries, 256 * 256);
-    __type(value, u16);
+    __type(value, u32);
     __type(key, u32);
 } left_stick SEC(".maps"), right_stick SEC(".maps");
 
+struct stick_buttons_params {
+    u32 buttons[4];   // Button masks of the up, right, down and left directions
+    u32 keep_analog;  // Keep reporting the analog value along with the buttons
+};
+
+struct stick_buttons_cfg {
+    __uint(type, BPF_MAP_TYPE_ARRAY);
+    __uint(max_entries, 2);
+    __type(value, struct stick_buttons_params);
+    __type(key, u32);
+} stick_buttons SEC(".maps");
+
+// D-pad modes (Must match the ones in 'input/stick.rs')
+#define DPAD_OFF 0
+#define DPAD_OVERRIDE 1
+#define DPAD_ADD 2
+
+struct dpad_params {
+    u32 mode;
+    s32 offsets[8][2]; // Raw offsets from the center indexed by the hat switch value
+};
+
+struct dpad_cfg {
+    __uint(type, BPF_MAP_TYPE_ARRAY);
+    __uint(max_entries, 2);
+    __type(value, struct dpad_params);
+    __type(key, u32);
+} dpad SEC(".maps");
+
+struct gyro_params {
+    u32 enabled;
+    s32 scale[2]; // Raw stick units per raw gyro unit of the X (yaw) and Y (pitch) axes (Q16)
+    u32 deadzone; // Speeds below this are ignored (Raw gyro units)
+    u32 button;   // Button mask that has to be held for the gyro to be enabled (0 if always enabled)
+};
+
+struct gyro_cfg {
+    __uint(type, BPF_MAP_TYPE_ARRAY);
+    __uint(max_entries, 1);
+    __type(value, struct gyro_params);
+    __type(key, u32);
+} gyro SEC(".maps");
+
+struct gyro_bias_cfg {
+    __uint(type, BPF_MAP_TYPE_ARRAY);
+    __uint(max_entries, 1);
+    __type(value, s32[3]); // Raw speed of each axis at rest
+    __type(key, u32);
+} gyro_bias SEC(".maps");
+
+struct motion_params {
+    u32 axes[3];  // Source axis index of each axis
+    s32 scale[3]; // Scale of each axis including the inversion (Q16)
+    u32 cutoff;   // Cutoff of the low-pass filter in rad/s (Q16, 0 if disabled)
+};
+
+struct motion_cfg {
+    __uint(type, BPF_MAP_TYPE_ARRAY);
+    __uint(max_entries, 2);
+    __type(value, struct motion_params);
+    __type(key, u32);
+} motion SEC(".maps");
+
+struct touchpad_params {
+    u32 enabled;
+    u32 min[2];   // Smallest reported coordinates
+    u32 max[2];   // Largest reported coordinates
+    u32 deadzone; // Movement smaller than this is ignored
+};
+
+struct touchpad_cfg {
+    __uint(type, BPF_MAP_TYPE_ARRAY);
+    __uint(max_entries, 1);
+    __type(value, struct touchpad_params);
+    __type(key, u32);
+} touchpad SEC(".maps");
+
+#define TOUCH_REGIONS_MAX 16 // Must match the one in 'input/touchpad.rs'
+
+struct touch_regions_params {
+    u32 grid[2]; // Number of columns and rows (0 if disabled)
+    u32 click;   // Regions get pressed by clicking instead of touching
+    u32 min[2];  // Smallest coordinates of the grid
+    u32 max[2];  // Largest coordinates of the grid
+    u32 buttons[TOUCH_REGIONS_MAX]; // Button masks of the regions in row-major order
+};
+
+struct touch_regions_cfg {
+    __uint(type, BPF_MAP_TYPE_ARRAY);
+    __uint(max_entries, 1);
+    __type(value, struct touch_regions_params);
+    __type(key, u32);
+} touch_regions SEC(".maps");
+
 struct trigger_lut {
     __uint(type, BPF_MAP_TYPE_ARRAY);
     __uint(max_entries, 256);
@@ -16,33 +116,239 @@ struct trigger_lut {
     __type(key, u32);
 } left_trigger SEC(".maps"), right_trigger SEC(".maps");
 
+// Smoothing modes (Must match the ones in 'input/smoothing.rs')
+#define SMOOTHING_AVERAGE 0
+#define SMOOTHING_EMA 1
+#define SMOOTHING_ONE_EURO 2
+
+#define NSEC_PER_SEC 1000000000ULL
+#define MIN_DELTA_TIME 100000ULL // 0.1ms
+
+struct smoothing_params {
+    u32 mode;
+    u32 window;     // Window size of the moving average
+    u32 time;       // Time window in ns of the moving average and EMA (0 if based on report count)
+    u32 alpha;      // Smoothing factor of the EMA (Q16)
+    u32 min_cutoff; // Minimum cutoff of the One-Euro filter in rad/s (Q16)
+    u32 beta;       // Cutoff increase per raw unit/s of speed in rad/s (Q16)
+    u32 d_cutoff;   // Cutoff of the speed in rad/s (Q16)
+};
+
 struct smoothing_cfg {
+    __uint(type, BPF_MAP_TYPE_ARRAY);
+    __uint(max_entries, 4);
+    __type(value, struct smoothing_params);
+    __type(key, u32);
+} smoothing SEC(".maps");
+
+struct jitter_cfg {
     __uint(type, BPF_MAP_TYPE_ARRAY);
     __uint(max_entries, 2);
     __type(value, u8);
     __type(key, u32);
-} smoothing SEC(".maps");
+} jitter SEC(".maps");
+
+struct digital_threshold {
+    u8 press;   // Value at which the button gets pressed
+    u8 release; // Value below which the button gets released
+};
 
-struct stick_smoothing {
+struct digital_cfg {
+    __uint(type, BPF_MAP_TYPE_ARRAY);
+    __uint(max_entries, 2);
+    __type(value, struct digital_threshold);
+    __type(key, u32);
+} digital SEC(".maps");
+
+struct hair_cfg {
+    __uint(type, BPF_MAP_TYPE_ARRAY);
+    __uint(max_entries, 2);
+    __type(value, u8);
+    __type(key, u32);
+} hair SEC(".maps");
+
+// Normalized button bits (Must match the ones in 'input/buttons.rs')
+#define BUTTON_DPAD_UP (1 << 0)
+#define BUTTON_DPAD_RIGHT (1 << 1)
+#define BUTTON_DPAD_DOWN (1 << 2)
+#define BUTTON_DPAD_LEFT (1 << 3)
+#define BUTTON_L2 (1 << 10)
+#define BUTTON_R2 (1 << 11)
+#define BUTTON_COUNT 19
+
+#define DS_BUTTONS2_MASK (DS_BUTTONS2_PS_HOME | DS_BUTTONS2_TOUCHPAD | DS_BUTTONS2_MIC_MUTE)
+
+struct remap_cfg {
+    __uint(type, BPF_MAP_TYPE_ARRAY);
+    __uint(max_entries, BUTTON_COUNT);
+    __type(value, u32); // Output button mask (0 if disabled)
+    __type(key, u32);   // Input button index
+} remap SEC(".maps");
+
+struct turbo_cfg {
+    __uint(type, BPF_MAP_TYPE_ARRAY);
+    __uint(max_entries, BUTTON_COUNT);
+    __type(value, u32); // Turbo period in ns (0 if disabled)
+    __type(key, u32);   // Button index
+} turbo SEC(".maps");
+
+struct toggle_cfg {
+    __uint(type, BPF_MAP_TYPE_ARRAY);
+    __uint(max_entries, 1);
+    __type(value, u32); // Mask of the toggled buttons
+    __type(key, u32);
+} toggle SEC(".maps");
+
+struct smoothing_state {
     u32 index;
     u32 count;
+    bool ready;
+    u64 time;
+    u64 times[256]; // Timestamps of the samples
     struct smoothing_axis {
         u8 arr[256];
         u32 sum;
+        s64 value; // Filtered value (Q16)
+        s64 delta; // Filtered speed in raw unit/s (Q16)
     } x, y;
-} ls_smoothing, rs_smoothing;
+} ls_smoothing, rs_smoothing, l2_smoothing, r2_smoothing;
+
+struct trigger_jitter {
+    u8 value;
+} l2_jitter, r2_jitter;
+
+struct trigger_hair {
+    u8 anchor; // Peak while pressed, valley while released
+    bool active;
+} l2_hair, r2_hair;
+
+struct trigger_digital {
+    bool pressed;
+} l2_digital, r2_digital;
+
+struct button_turbo {
+    u64 start[BUTTON_COUNT]; // Time of the press (0 if released)
+} turbo_state;
 
-void apply_stick(u8 *x, u8 *y, struct stick_lut *lut)
+struct button_toggle {
+    u32 previous; // Buttons held in the last report
+    u32 latched;
+} toggle_state;
+
+struct motion_state {
+    u64 time;
+    bool ready;
+    s64 value[3]; // Filtered value of each axis (Q16)
+} gyro_motion, accel_motion;
+
+struct touch_point_state {
+    bool active;
+    u8 id;
+    u16 x, y; // Last reported position
+} touch_points[2];
+
+struct stick_hysteresis {
+    bool active;
+} ls_hysteresis, rs_hysteresis;
+
+// Returns the directions past the button threshold
+u8 apply_stick(u8 *x, u8 *y, struct stick_lut *lut, struct stick_hysteresis *h)
 {
+    u8 directions = 0;
     u32 index = *x + *y * 256;
-    u16 *value = bpf_map_lookup_elem(lut, &index);
+    u32 *value = bpf_map_lookup_elem(lut, &index);
     if (value) {
-        u16 v = *value;
-        *x = v & 0x00FF;
-        *y = (v >> 8) & 0x00FF;
+        u32 v = *value;
+
+        // Activate only after passing the margin and deactivate inside the deadzone
+        if (v & STICK_LUT_DEADZONE) h->active = false;
+        else if (!(v & STICK_LUT_MARGIN)) h->active = true;
+
+        if (h->active || !(v & STICK_LUT_MARGIN)) {
+            *x = v & 0x00FF;
+            *y = (v >> 8) & 0x00FF;
+        } else {
+            *x = STICK_CENTER;
+            *y = STICK_CENTER;
+        }
+
+        directions = (v >> STICK_LUT_DIRECTIONS_SHIFT) & 0x0F;
     } else {
         bpf_printk("%s: Stick LUT value is NULL!", __func__);
     }
+    return directions;
+}
+
+u8 clamp_raw(s32 v) {
+    return v < 0 ? 0 : v > 0xFF ? 0xFF : v;
+}
+
+s16 clamp_s16(s64 v) {
+    return v < -0x8000 ? -0x8000 : v > 0x7FFF ? 0x7FFF : v;
+}
+
+void apply_dpad(u8 *x, u8 *y, u8 hat, u32 cfg)
+{
+    // Get the configured D-pad mode
+    struct dpad_params *p = bpf_map_lookup_elem(&dpad, &cfg);
+    if (!p || p->mode == DPAD_OFF || hat >= 8) return;
+
+    s32 base_x = p->mode == DPAD_ADD ? *x : STICK_CENTER;
+    s32 base_y = p->mode == DPAD_ADD ? *y : STICK_CENTER;
+    *x = clamp_raw(base_x + p->offsets[hat][0]);
+    *y = clamp_raw(base_y + p->offsets[hat][1]);
+}
+
+void apply_gyro_bias(__le16 *speeds)
+{
+    // Get the calibrated bias
+    u32 key = 0;
+    s32 *bias = bpf_map_lookup_elem(&gyro_bias, &key);
+    if (!bias) return;
+
+    for (u32 i = 0; i < 3; i++) {
+        speeds[i] = clamp_s16((s16)speeds[i] - bias[i]);
+    }
+}
+
+s32 gyro_offset(s16 speed, s32 scale, u32 deadzone) {
+    s32 abs = speed < 0 ? -(s32)speed : speed;
+    if ((u32)abs < deadzone) return 0;
+    return ((s64)speed * scale) >> 16;
+}
+
+void apply_gyro(u8 *x, u8 *y, __le16 *speeds, u32 buttons)
+{
+    // Get the gyro aiming parameters
+    u32 key = 0;
+    struct gyro_params *p = bpf_map_lookup_elem(&gyro, &key);
+    if (!p || !p->enabled) return;
+
+    // Ratchet with the button if configured
+    if (p->button && !(buttons & p->button)) return;
+
+    // Yaw drives X and pitch drives Y
+    *x = clamp_raw(*x + gyro_offset((s16)speeds[1], p->scale[0], p->deadzone));
+    *y = clamp_raw(*y + gyro_offset((s16)speeds[0], p->scale[1], p->deadzone));
+}
+
+u32 apply_stick_buttons(u8 *x, u8 *y, u8 directions, u32 cfg)
+{
+    // Get the configured buttons
+    struct stick_buttons_params *p = bpf_map_lookup_elem(&stick_buttons, &cfg);
+    if (!p) return 0;
+
+    u32 buttons = 0;
+    for (u32 i = 0; i < 4; i++) {
+        if (directions & (1 << i)) buttons |= p->buttons[i];
+    }
+
+    // Report the axes as centered if they're used only for the buttons (Up, Right, Down, Left)
+    if (!p->keep_analog) {
+        if (p->buttons[1] || p->buttons[3]) *x = STICK_CENTER;
+        if (p->buttons[0] || p->buttons[2]) *y = STICK_CENTER;
+    }
+    return buttons;
 }
 
 void apply_trigger(u8 *v, struct trigger_lut *lut)
@@ -62,23 +368,358 @@ void update_sum(struct smoothing_axis *v, u8 index, u8 input) {
     v->sum += input;
 }
 
-void apply_stick_smoothing(u8 *x, u8 *y, u32 cfg, struct stick_smoothing *s) {
-    // Get the configrued smooting value
-    u8 *amount = bpf_map_lookup_elem(&smoothing, &cfg);
-
-    // Only run if smoothing amount is 2 or more
-    if (amount && *amount > 1) {
+void apply_average(u8 *x, u8 *y, u32 window, struct smoothing_state *s) {
+    // Only run if the window is 2 or more
+    if (window > 1) {
         update_sum(&s->x, s->index, *x);
         update_sum(&s->y, s->index, *y);
 
         if (++s->index > s->count) s->count = s->index; // Increment index and count if needed
-        if (s->index > *amount) s->index = 0; // Wrap around index based on the configured value
+        if (s->index > window) s->index = 0; // Wrap around index based on the configured value
 
         *x = s->x.sum / s->count;
         *y = s->y.sum / s->count;
     }
 }
 
+void pop_sample(struct smoothing_state *s) {
+    u32 tail = (s->index - s->count) & 0xFF;
+    s->x.sum -= s->x.arr[tail];
+    s->y.sum -= s->y.arr[tail];
+    s->count--;
+}
+
+void apply_timed_average(u8 *x, u8 *y, u64 window, u64 now, struct smoothing_state *s) {
+    // Make room for the new sample if needed
+    if (s->count >= 256) pop_sample(s);
+
+    // Add the new sample
+    u32 head = s->index & 0xFF;
+    s->x.arr[head] = *x;
+    s->x.sum += *x;
+    s->y.arr[head] = *y;
+    s->y.sum += *y;
+    s->times[head] = now;
+    s->index = (head + 1) & 0xFF;
+    s->count++;
+
+    // Drop the samples older than the window but always keep the newest one
+    for (int i = 0; i < 256 && s->count > 1; i++) {
+        u32 tail = (s->index - s->count) & 0xFF;
+        if (now - s->times[tail] <= window) break;
+        pop_sample(s);
+    }
+
+    *x = s->x.sum / s->count;
+    *y = s->y.sum / s->count;
+}
+
+// Smoothing factor (Q16) of a low-pass filter with the cutoff (rad/s, Q16) over the elapsed time
+u64 cutoff_alpha(u64 cutoff, u64 dt) {
+    u64 wt = cutoff * dt / NSEC_PER_SEC;
+    return (wt << 16) / ((1 << 16) + wt);
+}
+
+void apply_motion(__le16 *values, u32 cfg, struct motion_state *s) {
+    // Get the configured transform
+    struct motion_params *p = bpf_map_lookup_elem(&motion, &cfg);
+    if (!p) return;
+
+    // Time elapsed since the last report
+    u64 now = bpf_ktime_get_ns();
+    u64 dt = now - s->time;
+    if (dt < MIN_DELTA_TIME) dt = MIN_DELTA_TIME;
+    if (dt > NSEC_PER_SEC) dt = NSEC_PER_SEC;
+    s->time = now;
+
+    s64 input[3] = { (s16)values[0], (s16)values[1], (s16)values[2] };
+    for (u32 i = 0; i < 3; i++) {
+        u32 axis = p->axes[i];
+        if (axis > 2) axis = i;
+        s64 out = (input[axis] * p->scale[i]) >> 16;
+
+        // Low-pass filter
+        if (p->cutoff) {
+            s64 target = out << 16;
+            if (!s->ready) s->value[i] = target;
+            else s->value[i] += ((target - s->value[i]) * (s64)cutoff_alpha(p->cutoff, dt)) >> 16;
+            out = (s->value[i] + 0x8000) >> 16;
+        }
+
+        values[i] = clamp_s16(out);
+    }
+    s->ready = true;
+}
+
+void ema(struct smoothing_axis *v, u8 *input, u64 alpha) {
+    s64 target = (s64)*input << 16;
+    v->value += ((target - v->value) * (s64)alpha) >> 16;
+    *input = (v->value + 0x8000) >> 16;
+}
+
+void one_euro(struct smoothing_axis *v, u8 *input, struct smoothing_params *p, u64 dt) {
+    s64 target = (s64)*input << 16;
+    s64 diff = target - v->value;
+
+    // Speed of the input, filtered to reduce jitter
+    u64 abs_diff = diff < 0 ? -diff : diff;
+    s64 delta = abs_diff * NSEC_PER_SEC / dt;
+    if (diff < 0) delta = -delta;
+    v->delta += ((delta - v->delta) * (s64)cutoff_alpha(p->d_cutoff, dt)) >> 16;
+
+    // Raise the cutoff with speed to reduce lag
+    u64 speed = v->delta < 0 ? -v->delta : v->delta;
+    u64 cutoff = p->min_cutoff + ((p->beta * speed) >> 16);
+    if (cutoff > 0xFFFFFFFF) cutoff = 0xFFFFFFFF;
+
+    v->value += (diff * (s64)cutoff_alpha(cutoff, dt)) >> 16;
+    *input = (v->value + 0x8000) >> 16;
+}
+
+void apply_smoothing(u8 *x, u8 *y, u32 cfg, struct smoothing_state *s) {
+    // Get the configured smoothing parameters
+    struct smoothing_params *p = bpf_map_lookup_elem(&smoothing, &cfg);
+    if (!p) return;
+
+    // Time elapsed since the last report
+    u64 now = bpf_ktime_get_ns();
+    u64 dt = now - s->time;
+    if (dt < MIN_DELTA_TIME) dt = MIN_DELTA_TIME;
+    if (dt > NSEC_PER_SEC) dt = NSEC_PER_SEC;
+    s->time = now;
+
+    if (p->mode == SMOOTHING_AVERAGE && p->time > 0) {
+        apply_timed_average(x, y, p->time, now, s);
+    } else if (p->mode == SMOOTHING_AVERAGE) {
+        apply_average(x, y, p->window, s);
+    } else if (!s->ready) {
+        // Start the filters from the first input
+        s->x.value = (s64)*x << 16;
+        s->y.value = (s64)*y << 16;
+        s->ready = true;
+    } else if (p->mode == SMOOTHING_EMA) {
+        // Time constant of half the window has the same center of mass as the average
+        u64 alpha = p->time > 0 ? (dt << 16) / (p->time / 2 + dt) : p->alpha;
+        ema(&s->x, x, alpha);
+        ema(&s->y, y, alpha);
+    } else if (p->mode == SMOOTHING_ONE_EURO) {
+        one_euro(&s->x, x, p, dt);
+        one_euro(&s->y, y, p, dt);
+    }
+}
+
+void apply_trigger_smoothing(u8 *v, u32 cfg, struct smoothing_state *s) {
+    u8 unused = 0;
+    apply_smoothing(v, &unused, cfg, s);
+}
+
+void apply_trigger_jitter(u8 *v, u32 cfg, struct trigger_jitter *j) {
+    // Get the configured jitter threshold
+    u8 *threshold = bpf_map_lookup_elem(&jitter, &cfg);
+
+    if (threshold && *threshold > 0) {
+        // Keep the previous value unless it changed enough or reached either end
+        u8 diff = *v > j->value ? *v - j->value : j->value - *v;
+        if (diff >= *threshold || *v == 0 || *v == 0xFF) j->value = *v;
+        *v = j->value;
+    }
+}
+
+void apply_trigger_hair(u8 *v, u32 cfg, struct trigger_hair *h) {
+    // Get the configured travel (zero if not in hair mode)
+    u8 *travel = bpf_map_lookup_elem(&hair, &cfg);
+    if (!travel || *travel == 0) return;
+
+    if (*v == 0) {
+        // Fully released
+        h->active = false;
+        h->anchor = 0;
+    } else if (h->active) {
+        // Release after moving up from the peak
+        if (*v > h->anchor) h->anchor = *v;
+        else if (h->anchor - *v >= *travel) {
+            h->active = false;
+            h->anchor = *v;
+        }
+    } else {
+        // Press after moving down from the valley
+        if (*v < h->anchor) h->anchor = *v;
+        else if (*v - h->anchor >= *travel) {
+            h->active = true;
+            h->anchor = *v;
+        }
+    }
+
+    *v = h->active ? 0xFF : 0;
+}
+
+bool trigger_pressed(u8 v, u32 cfg, struct trigger_digital *d) {
+    // Get the configured press and release levels
+    struct digital_threshold *t = bpf_map_lookup_elem(&digital, &cfg);
+    if (!t) return v > 0;
+
+    if (v >= t->press) d->pressed = true;
+    else if (v < t->release) d->pressed = false;
+    return d->pressed;
+}
+
+// D-pad bits of the hat switch values (8 is neutral)
+static const u8 hat_to_dpad[9] = {
+    BUTTON_DPAD_UP,
+    BUTTON_DPAD_UP | BUTTON_DPAD_RIGHT,
+    BUTTON_DPAD_RIGHT,
+    BUTTON_DPAD_DOWN | BUTTON_DPAD_RIGHT,
+    BUTTON_DPAD_DOWN,
+    BUTTON_DPAD_DOWN | BUTTON_DPAD_LEFT,
+    BUTTON_DPAD_LEFT,
+    BUTTON_DPAD_UP | BUTTON_DPAD_LEFT,
+    0,
+};
+
+// Hat switch values of the D-pad bits (Opposite directions cancel out)
+static const u8 dpad_to_hat[16] = {
+    8, 0, 2, 1, 4, 8, 3, 2, 6, 7, 8, 0, 5, 6, 4, 8,
+};
+
+u32 get_buttons(struct dualsense_input_report *input) {
+    u8 hat = input->buttons[0] & DS_BUTTONS0_HAT_SWITCH;
+    u32 buttons = hat < 8 ? hat_to_dpad[hat] : 0;
+    buttons |= input->buttons[0] & ~DS_BUTTONS0_HAT_SWITCH;
+    buttons |= (u32)input->buttons[1] << 8;
+    buttons |= (u32)(input->buttons[2] & DS_BUTTONS2_MASK) << 16;
+    return buttons;
+}
+
+void set_buttons(struct dualsense_input_report *input, u32 buttons) {
+    input->buttons[0] = (buttons & ~DS_BUTTONS0_HAT_SWITCH & 0xFF) | dpad_to_hat[buttons & 0x0F];
+    input->buttons[1] = buttons >> 8;
+    input->buttons[2] &= ~DS_BUTTONS2_MASK;
+    input->buttons[2] |= (buttons >> 16) & DS_BUTTONS2_MASK;
+}
+
+u32 apply_remap(u32 buttons) {
+    u32 output = 0;
+    for (u32 i = 0; i < BUTTON_COUNT; i++) {
+        if (!(buttons & (1 << i))) continue;
+        u32 *mask = bpf_map_lookup_elem(&remap, &i);
+        output |= mask ? *mask : (1 << i);
+    }
+    return output;
+}
+
+u32 apply_turbo(u32 buttons, struct button_turbo *t) {
+    u64 now = bpf_ktime_get_ns();
+    for (u32 i = 0; i < BUTTON_COUNT; i++) {
+        u32 mask = 1 << i;
+        u32 *period = bpf_map_lookup_elem(&turbo, &i);
+        if (!period || *period == 0 || !(buttons & mask)) {
+            t->start[i] = 0;
+            continue;
+        }
+
+        // Pressed for the first half of each period starting from the press
+        if (t->start[i] == 0) t->start[i] = now;
+        if ((now - t->start[i]) % *period >= *period / 2) buttons &= ~mask;
+    }
+    return buttons;
+}
+
+u32 toggle_mask() {
+    u32 key = 0;
+    u32 *mask = bpf_map_lookup_elem(&toggle, &key);
+    return mask ? *mask : 0;
+}
+
+u32 apply_toggle(u32 buttons, u32 mask, struct button_toggle *t) {
+    // Flip the latch on every new press
+    t->latched ^= buttons & ~t->previous & mask;
+    t->previous = buttons;
+    return (buttons & ~mask) | (t->latched & mask);
+}
+
+void apply_touch_point(struct dualsense_touch_point *point, struct touchpad_params *p, struct touch_point_state *s)
+{
+    u16 x = point->x_lo | (point->x_hi << 8);
+    u16 y = point->y_lo | (point->y_hi << 4);
+    u8 id = point->contact & ~DS_TOUCH_POINT_INACTIVE;
+    bool active = !(point->contact & DS_TOUCH_POINT_INACTIVE);
+
+    // Drop the touches when disabled or on the edges
+    if (!p->enabled || x < p->min[0] || x > p->max[0] || y < p->min[1] || y > p->max[1])
+        active = false;
+
+    if (!active) {
+        point->contact |= DS_TOUCH_POINT_INACTIVE;
+        x = 0;
+        y = 0;
+        s->active = false;
+    } else if (s->active && s->id == id) {
+        // Keep the last position until it moves past the deadzone
+        s32 dx = x - s->x;
+        s32 dy = y - s->y;
+        if ((u32)(dx * dx + dy * dy) <= p->deadzone * p->deadzone) {
+            x = s->x;
+            y = s->y;
+        } else {
+            s->x = x;
+            s->y = y;
+        }
+    } else {
+        // New touch
+        s->active = true;
+        s->id = id;
+        s->x = x;
+        s->y = y;
+    }
+
+    point->x_lo = x & 0xFF;
+    point->x_hi = (x >> 8) & 0x0F;
+    point->y_lo = y & 0x0F;
+    point->y_hi = (y >> 4) & 0xFF;
+}
+
+u32 touch_region_buttons(struct dualsense_input_report *input)
+{
+    // Get the configured regions
+    u32 key = 0;
+    struct touch_regions_params *p = bpf_map_lookup_elem(&touch_regions, &key);
+    if (!p || !p->grid[0] || !p->grid[1]) return 0;
+    if (p->click && !(input->buttons[2] & DS_BUTTONS2_TOUCHPAD)) return 0;
+
+    u32 buttons = 0;
+    for (u32 i = 0; i < 2; i++) {
+        struct dualsense_touch_point *point = &input->points[i];
+        if (point->contact & DS_TOUCH_POINT_INACTIVE) continue;
+
+        u32 x = point->x_lo | (point->x_hi << 8);
+        u32 y = point->y_lo | (point->y_hi << 4);
+        if (x < p->min[0] || x > p->max[0] || y < p->min[1] || y > p->max[1]) continue;
+
+        // The grid covers the area inside the masked edges
+        u32 column = (x - p->min[0]) * p->grid[0] / (p->max[0] - p->min[0] + 1);
+        u32 row = (y - p->min[1]) * p->grid[1] / (p->max[1] - p->min[1] + 1);
+        if (column >= p->grid[0] || row >= p->grid[1]) continue;
+
+        u32 region = row * p->grid[0] + column;
+        if (region < TOUCH_REGIONS_MAX) buttons |= p->buttons[region];
+    }
+
+    // The region replaces the touchpad click
+    if (p->click && buttons) input->buttons[2] &= ~DS_BUTTONS2_TOUCHPAD;
+    return buttons;
+}
+
+void apply_touchpad(struct dualsense_input_report *input)
+{
+    // Get the touchpad parameters
+    u32 key = 0;
+    struct touchpad_params *p = bpf_map_lookup_elem(&touchpad, &key);
+    if (!p) return;
+
+    apply_touch_point(&input->points[0], p, &touch_points[0]);
+    apply_touch_point(&input->points[1], p, &touch_points[1]);
+}
+
 SEC("struct_ops/hid_device_event")
 int BPF_PROG(mod_device_event, struct hid_bpf_ctx *hid_ctx)
 {
@@ -109,19 +750,74 @@ int BPF_PROG(mod_device_event, struct hid_bpf_ctx *hid_ctx)
     if (is_bt && !check_crc(data, DS_INPUT_REPORT_BT_SIZE))
         return 0; // Skip on incorrect CRC
 
+    // Remove the gyro bias
+    apply_gyro_bias(input->gyro);
+
+    // Remap and filter the motion sensors
+    apply_motion(input->gyro, 0, &gyro_motion);
+    apply_motion(input->accel, 1, &accel_motion);
+
+    // Filter the touchpad
+    apply_touchpad(input);
+
+    // Get the buttons of the touchpad regions from the filtered touches
+    u32 touch_pressed = touch_region_buttons(input);
+
+    // Filter out trigger jitter
+    apply_trigger_jitter(&input->z, 0, &l2_jitter);
+    apply_trigger_jitter(&input->rz, 1, &r2_jitter);
+
+    // Drive the sticks with the D-pad
+    u8 hat = input->buttons[0] & DS_BUTTONS0_HAT_SWITCH;
+    apply_dpad(&input->x, &input->y, hat, 0);
+    apply_dpad(&input->rx, &input->ry, hat, 1);
+
     // Apply LUT values
-    apply_stick(&input->x, &input->y, &left_stick);
-    apply_stick(&input->rx, &input->ry, &right_stick);
+    u8 ls_directions = apply_stick(&input->x, &input->y, &left_stick, &ls_hysteresis);
+    u8 rs_directions = apply_stick(&input->rx, &input->ry, &right_stick, &rs_hysteresis);
     apply_trigger(&input->z, &left_trigger);
     apply_trigger(&input->rz, &right_trigger);
 
+    // Get the buttons emitted by the sticks
+    u32 stick_pressed = apply_stick_buttons(&input->x, &input->y, ls_directions, 0)
+                      | apply_stick_buttons(&input->rx, &input->ry, rs_directions, 1);
+
     // Apply Smoothing
-    apply_stick_smoothing(&input->x, &input->y, 0, &ls_smoothing);
-    apply_stick_smoothing(&input->rx, &input->ry, 1, &rs_smoothing);
+    apply_smoothing(&input->x, &input->y, 0, &ls_smoothing);
+    apply_smoothing(&input->rx, &input->ry, 1, &rs_smoothing);
+    apply_trigger_smoothing(&input->z, 2, &l2_smoothing);
+    apply_trigger_smoothing(&input->rz, 3, &r2_smoothing);
+
+    // Apply hair trigger
+    apply_trigger_hair(&input->z, 0, &l2_hair);
+    apply_trigger_hair(&input->rz, 1, &r2_hair);
 
     // Recalculate trigger press treshold
     input->buttons[1] &= (DS_BUTTONS1_L2 | DS_BUTTONS1_R2) ^ 0xFF;
-    input->buttons[1] |= DS_BUTTONS1_L2 * (input->z > 0) + DS_BUTTONS1_R2 * (input->rz > 0);
+    input->buttons[1] |= DS_BUTTONS1_L2 * trigger_pressed(input->z, 0, &l2_digital)
+                       + DS_BUTTONS1_R2 * trigger_pressed(input->rz, 1, &r2_digital);
+
+    u32 buttons = get_buttons(input) | touch_pressed | stick_pressed;
+
+    // Add the gyro speed to the right stick
+    apply_gyro(&input->rx, &input->ry, input->gyro, buttons);
+
+    // Remap and disable buttons (Including the ones of the sticks and touchpad regions)
+    buttons = apply_remap(buttons);
+
+    // Apply toggles (Latches the analog triggers as well)
+    u32 toggled = toggle_mask();
+    buttons = apply_toggle(buttons, toggled, &toggle_state);
+    if (toggled & BUTTON_L2) input->z = buttons & BUTTON_L2 ? 0xFF : 0;
+    if (toggled & BUTTON_R2) input->rz = buttons & BUTTON_R2 ? 0xFF : 0;
+
+    // Apply turbo (Releases the analog triggers as well)
+    u32 held = buttons;
+    buttons = apply_turbo(buttons, &turbo_state);
+    if (held & ~buttons & BUTTON_L2) input->z = 0;
+    if (held & ~buttons & BUTTON_R2) input->rz = 0;
+
+    set_buttons(input, buttons);
 
     // Update CRC if using Bluetooth
     if (is_bt) update_crc(data, DS_INPUT_REPORT_BT_SIZE);
@@ -12,6 +12,7 @@
 #define PS_INPUT_CRC32_SEED 0xA1;
 
 /* Button masks for DualSense input report. */
+#define DS_BUTTONS0_HAT_SWITCH	0x0F
 #define DS_BUTTONS0_SQUARE		(1 << 4)
 #define DS_BUTTONS0_CROSS		(1 << 5)
 #define DS_BUTTONS0_CIRCLE		(1 << 6)
@@ -28,6 +29,11 @@
 #define DS_BUTTONS2_TOUCHPAD	(1 << 1)
 #define DS_BUTTONS2_MIC_MUTE	(1 << 2)
 
+/* Touchpad */
+#define DS_TOUCH_POINT_INACTIVE	(1 << 7)
+#define DS_TOUCHPAD_WIDTH		1920
+#define DS_TOUCHPAD_HEIGHT		1080
+
 #define PACKED __attribute__((__packed__))
 
 struct PACKED dualsense_touch_point {
@@ -2,13 +2,13 @@ mod skel {
     include!(concat!(env!("OUT_DIR"), "/dualsense.skel.rs"));
 }
 
-use crate::conf::Config;
+use crate::conf::{Calibration, Config};
 use anyhow::{Result, anyhow};
 use libbpf_rs::skel::{OpenSkel, SkelBuilder};
 use libbpf_rs::{Link, MapCore, MapFlags};
 use std::mem::MaybeUninit;
 
-pub fn load(sysname: &str, config: &Config) -> Result<Link> {
+pub fn load(sysname: &str, config: &Config, calibration: &Calibration) -> Result<Link> {
     let builder = skel::DualsenseSkelBuilder::default();
     let mut open_object = MaybeUninit::uninit();
     let mut open_skel = builder.open(&mut open_object)?;
@@ -17,11 +17,26 @@ pub fn load(sysname: &str, config: &Config) -> Result<Link> {
 
     let mut skel = open_skel.load()?;
 
-    update_stick_lut(skel.maps.left_stick, &config.stick.left.gen_lut())?;
-    update_stick_lut(skel.maps.right_stick, &config.stick.right.gen_lut())?;
+    let left_stick = config.stick.left.gen_lut(&calibration.stick.left);
+    let right_stick = config.stick.right.gen_lut(&calibration.stick.right);
+    update_stick_lut(skel.maps.left_stick, &left_stick)?;
+    update_stick_lut(skel.maps.right_stick, &right_stick)?;
     update_trigger_lut(skel.maps.left_trigger, &config.trigger.left.gen_lut())?;
     update_trigger_lut(skel.maps.right_trigger, &config.trigger.right.gen_lut())?;
     update_smoothing(skel.maps.smoothing, config)?;
+    update_jitter(skel.maps.jitter, config)?;
+    update_digital(skel.maps.digital, config)?;
+    update_hair(skel.maps.hair, config)?;
+    update_remap(skel.maps.remap, config)?;
+    update_turbo(skel.maps.turbo, config)?;
+    update_toggle(skel.maps.toggle, config)?;
+    update_stick_buttons(skel.maps.stick_buttons, config)?;
+    update_dpad(skel.maps.dpad, config)?;
+    update_gyro(skel.maps.gyro, config)?;
+    update_gyro_bias(skel.maps.gyro_bias, calibration)?;
+    update_motion(skel.maps.motion, config)?;
+    update_touchpad(skel.maps.touchpad, config)?;
+    update_touch_regions(skel.maps.touch_regions, config)?;
 
     Ok(skel.maps.dstuner.attach_struct_ops()?)
 }
@@ -50,7 +65,7 @@ pub fn sysnum(sysname: &str) -> Option<u32> {
     u32::from_str_radix(&sysname[start..], 16).ok()
 }
 
-fn update_stick_lut<M: MapCore>(map: M, lut: &[u16]) -> libbpf_rs::Result<()> {
+fn update_stick_lut<M: MapCore>(map: M, lut: &[u32]) -> libbpf_rs::Result<()> {
     debug_assert_eq!(lut.len(), 256 * 256);
     for (k, v) in lut.iter().enumerate() {
         let key = (k as u32).to_ne_bytes();
@@ -71,15 +86,155 @@ fn update_trigger_lut<M: MapCore>(map: M, lut: &[u8]) -> libbpf_rs::Result<()> {
 }
 
 fn update_smoothing<M: MapCore>(map: M, config: &Config) -> libbpf_rs::Result<()> {
+    let Config { stick, trigger, .. } = config;
+    let params = [
+        stick.left.smoothing_params(),
+        stick.right.smoothing_params(),
+        trigger.left.smoothing_params(),
+        trigger.right.smoothing_params(),
+    ];
+
+    // Left Stick, Right Stick, Left Trigger, Right Trigger
+    for (key, params) in params.iter().enumerate() {
+        let key = (key as u32).to_ne_bytes();
+        map.update(&key, &params.to_ne_bytes(), MapFlags::ANY)?;
+    }
+    Ok(())
+}
+
+fn update_jitter<M: MapCore>(map: M, config: &Config) -> libbpf_rs::Result<()> {
+    map.update(
+        &0u32.to_ne_bytes(), // Left Trigger
+        &config.trigger.left.jitter_threshold().to_ne_bytes(),
+        MapFlags::ANY,
+    )?;
+    map.update(
+        &1u32.to_ne_bytes(), // Right Trigger
+        &config.trigger.right.jitter_threshold().to_ne_bytes(),
+        MapFlags::ANY,
+    )?;
+    Ok(())
+}
+
+fn update_digital<M: MapCore>(map: M, config: &Config) -> libbpf_rs::Result<()> {
+    map.update(
+        &0u32.to_ne_bytes(), // Left Trigger
+        &config.trigger.left.digital_threshold.to_raw(),
+        MapFlags::ANY,
+    )?;
+    map.update(
+        &1u32.to_ne_bytes(), // Right Trigger
+        &config.trigger.right.digital_threshold.to_raw(),
+        MapFlags::ANY,
+    )?;
+    Ok(())
+}
+
+fn update_hair<M: MapCore>(map: M, config: &Config) -> libbpf_rs::Result<()> {
+    map.update(
+        &0u32.to_ne_bytes(), // Left Trigger
+        &config.trigger.left.hair_travel().to_ne_bytes(),
+        MapFlags::ANY,
+    )?;
+    map.update(
+        &1u32.to_ne_bytes(), // Right Trigger
+        &config.trigger.right.hair_travel().to_ne_bytes(),
+        MapFlags::ANY,
+    )?;
+    Ok(())
+}
+
+fn update_remap<M: MapCore>(map: M, config: &Config) -> libbpf_rs::Result<()> {
+    for (key, mask) in config.buttons.remap_table().iter().enumerate() {
+        let key = (key as u32).to_ne_bytes();
+        map.update(&key, &mask.to_ne_bytes(), MapFlags::ANY)?;
+    }
+    Ok(())
+}
+
+fn update_turbo<M: MapCore>(map: M, config: &Config) -> libbpf_rs::Result<()> {
+    for (key, period) in config.turbo.period_table().iter().enumerate() {
+        let key = (key as u32).to_ne_bytes();
+        map.update(&key, &period.to_ne_bytes(), MapFlags::ANY)?;
+    }
+    Ok(())
+}
+
+fn update_toggle<M: MapCore>(map: M, config: &Config) -> libbpf_rs::Result<()> {
+    map.update(
+        &0u32.to_ne_bytes(),
+        &config.toggle.mask().to_ne_bytes(),
+        MapFlags::ANY,
+    )
+}
+
+fn update_stick_buttons<M: MapCore>(map: M, config: &Config) -> libbpf_rs::Result<()> {
     map.update(
         &0u32.to_ne_bytes(), // Left Stick
-        &config.stick.left.smoothing.to_ne_bytes(),
+        &config.stick.left.buttons.to_ne_bytes(),
         MapFlags::ANY,
     )?;
     map.update(
         &1u32.to_ne_bytes(), // Right Stick
-        &config.stick.right.smoothing.to_ne_bytes(),
+        &config.stick.right.buttons.to_ne_bytes(),
         MapFlags::ANY,
     )?;
     Ok(())
 }
+
+fn update_dpad<M: MapCore>(map: M, config: &Config) -> libbpf_rs::Result<()> {
+    map.update(
+        &0u32.to_ne_bytes(), // Left Stick
+        &config.stick.left.dpad_params().to_ne_bytes(),
+        MapFlags::ANY,
+    )?;
+    map.update(
+        &1u32.to_ne_bytes(), // Right Stick
+        &config.stick.right.dpad_params().to_ne_bytes(),
+        MapFlags::ANY,
+    )?;
+    Ok(())
+}
+
+fn update_gyro<M: MapCore>(map: M, config: &Config) -> libbpf_rs::Result<()> {
+    map.update(
+        &0u32.to_ne_bytes(),
+        &config.gyro.params().to_ne_bytes(),
+        MapFlags::ANY,
+    )
+}
+
+fn update_gyro_bias<M: MapCore>(map: M, calibration: &Calibration) -> libbpf_rs::Result<()> {
+    map.update(
+        &0u32.to_ne_bytes(),
+        &calibration.gyro.to_ne_bytes(),
+        MapFlags::ANY,
+    )
+}
+
+fn update_motion<M: MapCore>(map: M, config: &Config) -> libbpf_rs::Result<()> {
+    let params = [config.motion.gyro.params(), config.motion.accel.params()];
+
+    // Gyro, Accelerometer
+    for (key, params) in params.iter().enumerate() {
+        let key = (key as u32).to_ne_bytes();
+        map.update(&key, &params.to_ne_bytes(), MapFlags::ANY)?;
+    }
+    Ok(())
+}
+
+fn update_touchpad<M: MapCore>(map: M, config: &Config) -> libbpf_rs::Result<()> {
+    map.update(
+        &0u32.to_ne_bytes(),
+        &config.touchpad.params().to_ne_bytes(),
+        MapFlags::ANY,
+    )
+}
+
+fn update_touch_regions<M: MapCore>(map: M, config: &Config) -> libbpf_rs::Result<()> {
+    map.update(
+        &0u32.to_ne_bytes(),
+        &config.touchpad.region_params().to_ne_bytes(),
+        MapFlags::ANY,
+    )
+}
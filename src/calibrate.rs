@@ -0,0 +1,87 @@
+use crate::input::{GyroBias, GyroCalibration, parse_gyro};
+use anyhow::{Result, anyhow, bail};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Measures the gyro bias of every connected controller.
+pub fn gyro(config_path: &Path, duration: f64, write: bool) -> Result<()> {
+    if !(duration > 0.0 && duration <= 60.0) {
+        bail!("Duration must be more than 0 and at most 60 seconds! (got {duration})");
+    }
+
+    let sysnames = crate::device::list()?;
+    if sysnames.is_empty() {
+        bail!("No DualSense controller found!");
+    }
+
+    for sysname in sysnames {
+        let uniq = crate::device::uniq(&sysname);
+        let id = uniq.as_deref().unwrap_or("unknown");
+        log::info!("Calibrating {sysname} (ID: {id}). Keep the controller still for {duration} s.");
+
+        let calibration = measure(&sysname, Duration::from_secs_f64(duration))?;
+        let table = gyro_table(uniq.as_deref().unwrap_or("<ID>"), &calibration);
+        log::info!("Gyro calibration of {sysname}:\n{table}");
+
+        match uniq {
+            Some(uniq) if write => append(config_path, &uniq, &table)?,
+            None if write => log::warn!("Can't save the calibration without the controller's ID!"),
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+fn measure(sysname: &str, duration: Duration) -> Result<GyroCalibration> {
+    let path =
+        crate::device::hidraw(sysname).ok_or(anyhow!("Failed to find the hidraw device!"))?;
+    let mut file = File::open(&path)?;
+
+    let mut bias = GyroBias::default();
+    let mut skipped = 0;
+    let mut report = [0u8; 128];
+    let start = Instant::now();
+    while start.elapsed() < duration {
+        let len = file.read(&mut report)?;
+        // Other and corrupted reports would skew the bias
+        match parse_gyro(&report[..len]) {
+            Some(speeds) => bias.add(speeds),
+            None => skipped += 1,
+        }
+    }
+
+    if bias.moved() {
+        bail!("The controller was moved during calibration!");
+    }
+    log::debug!(
+        "Collected {} gyro samples ({skipped} skipped)",
+        bias.count()
+    );
+    bias.calibration()
+        .ok_or(anyhow!("No gyro samples were received!"))
+}
+
+fn gyro_table(uniq: &str, calibration: &GyroCalibration) -> String {
+    let [x, y, z] = calibration.offset;
+    format!("[calibration.\"{uniq}\".gyro]\noffset = [{x:.2}, {y:.2}, {z:.2}]\n")
+}
+
+fn append(config_path: &Path, uniq: &str, table: &str) -> Result<()> {
+    // Appending a table a second time would make the config invalid
+    let config = crate::conf::load(config_path)?;
+    if config
+        .calibration
+        .get(uniq)
+        .is_some_and(|c| c.gyro != GyroCalibration::default())
+    {
+        bail!("The config already has a gyro calibration for {uniq}! Remove it first.");
+    }
+
+    let mut file = OpenOptions::new().append(true).open(config_path)?;
+    write!(file, "\n{table}")?;
+    log::info!("Saved the gyro calibration to {}", config_path.display());
+    Ok(())
+}
@@ -33,4 +33,18 @@ pub enum Commands {
         #[arg(short, long, value_name = "FILE", default_value = "./ds-tuner.toml")]
         config: PathBuf,
     },
+    /// Measure the gyro bias of the connected controllers while they rest
+    CalibrateGyro {
+        /// Path to the config file
+        #[arg(short, long, value_name = "FILE", default_value = "./ds-tuner.toml")]
+        config: PathBuf,
+
+        /// Duration of the measurement in seconds
+        #[arg(short, long, default_value_t = 5.0)]
+        duration: f64,
+
+        /// Append the calibration to the config file
+        #[arg(short, long)]
+        write: bool,
+    },
 }
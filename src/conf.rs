@@ -1,7 +1,11 @@
-use crate::input::{StickOptions, TriggerOptions};
+use crate::input::{
+    ButtonOptions, GyroCalibration, GyroOptions, MotionOptions, StickCalibration, StickOptions,
+    ToggleOptions, TouchpadOptions, TriggerOptions, TurboOptions,
+};
 use crate::service::Event;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::mpsc::SyncSender;
 use std::sync::{Arc, Mutex, MutexGuard};
@@ -20,11 +24,76 @@ pub struct Triggers {
     pub right: TriggerOptions,
 }
 
+#[derive(Debug, Default, Deserialize, PartialEq, Clone)]
+#[serde(default)]
+pub struct StickCalibrations {
+    pub left: StickCalibration,
+    pub right: StickCalibration,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Clone)]
+#[serde(default)]
+pub struct Calibration {
+    pub stick: StickCalibrations,
+    pub gyro: GyroCalibration,
+}
+
 #[derive(Debug, Default, Deserialize, PartialEq)]
 #[serde(default)]
 pub struct Config {
     pub stick: Sticks,
     pub trigger: Triggers,
+    pub buttons: ButtonOptions,
+    pub turbo: TurboOptions,
+    pub toggle: ToggleOptions,
+    pub gyro: GyroOptions,
+    pub motion: MotionOptions,
+    pub touchpad: TouchpadOptions,
+    /// Calibration data keyed by the controllers' unique ID.
+    pub calibration: HashMap<String, Calibration>,
+}
+
+impl Config {
+    /// Checks every section and the stick calibration of every controller.
+    pub fn validate(&self) -> Result<()> {
+        let Config {
+            stick,
+            trigger,
+            turbo,
+            gyro,
+            motion,
+            touchpad,
+            calibration,
+            ..
+        } = self;
+        stick.left.validate().context("Invalid [stick.left]")?;
+        stick.right.validate().context("Invalid [stick.right]")?;
+        trigger.left.validate().context("Invalid [trigger.left]")?;
+        trigger
+            .right
+            .validate()
+            .context("Invalid [trigger.right]")?;
+        turbo.validate().context("Invalid [turbo]")?;
+        gyro.validate().context("Invalid [gyro]")?;
+        motion.validate().context("Invalid [motion]")?;
+        touchpad.validate().context("Invalid [touchpad]")?;
+        for (uniq, calibration) in calibration {
+            let StickCalibrations { left, right } = &calibration.stick;
+            left.validate()
+                .with_context(|| format!("Invalid [calibration.\"{uniq}\".stick.left]"))?;
+            right
+                .validate()
+                .with_context(|| format!("Invalid [calibration.\"{uniq}\".stick.right]"))?;
+        }
+        Ok(())
+    }
+
+    /// Returns the calibration data of the controller or the default if there is none.
+    pub fn calibration(&self, uniq: Option<&str>) -> Calibration {
+        uniq.and_then(|uniq| self.calibration.get(uniq))
+            .cloned()
+            .unwrap_or_default()
+    }
 }
 
 pub struct ConfigWatcher {
@@ -58,14 +127,16 @@ fn try_load<P: AsRef<Path>>(path: P, mutex: &Mutex<Config>) -> bool {
                 return true;
             }
         }
-        Err(error) => log::error!("Failed to load configuration file: {error}"),
+        Err(error) => log::error!("Failed to load configuration file: {error:#}"),
     }
     false
 }
 
-fn load<P: AsRef<Path>>(path: P) -> Result<Config> {
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Config> {
     let toml_str = std::fs::read_to_string(path.as_ref())?;
-    Ok(toml::from_str(&toml_str)?)
+    let config: Config = toml::from_str(&toml_str)?;
+    config.validate()?;
+    Ok(config)
 }
 
 fn spawn_watcher(path: PathBuf, tx: SyncSender<Event>, config: Arc<Mutex<Config>>) {
@@ -2,6 +2,7 @@ use crate::service::Event;
 use anyhow::Result;
 use std::ffi::OsStr;
 use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
 use std::sync::mpsc::SyncSender;
 use udev::mio::{Events, Interest, Poll, Token};
 
@@ -37,17 +38,41 @@ pub fn check_sysname(name: &OsStr) -> bool {
     false
 }
 
-fn query(tx: SyncSender<Event>) -> Result<()> {
+/// Gets the unique ID (MAC address) of the device.
+pub fn uniq(sysname: &str) -> Option<String> {
+    let device = udev::Device::from_subsystem_sysname(SUBSYSTEM.into(), sysname.into()).ok()?;
+    let uniq = device.property_value("HID_UNIQ")?.to_str()?;
+    (!uniq.is_empty()).then(|| uniq.to_string())
+}
+
+/// Gets the hidraw device node of the device.
+pub fn hidraw(sysname: &str) -> Option<PathBuf> {
+    let device = udev::Device::from_subsystem_sysname(SUBSYSTEM.into(), sysname.into()).ok()?;
+
+    let mut query = udev::Enumerator::new().ok()?;
+    query.match_parent(&device).ok()?;
+    query.match_subsystem("hidraw").ok()?;
+
+    let mut list = query.scan_devices().ok()?;
+    list.find_map(|device| device.devnode().map(PathBuf::from))
+}
+
+/// Lists the sysnames of the connected supported devices.
+pub fn list() -> Result<Vec<String>> {
     let mut query = udev::Enumerator::new()?;
     query.match_subsystem(SUBSYSTEM)?;
 
     let list = query.scan_devices()?;
-    for device in list {
-        if check_sysname(device.sysname()) {
-            let sysname = to_str(device.sysname());
-            log::debug!("Found device: {sysname}");
-            tx.send(Event::DeviceAdded(sysname))?;
-        }
+    Ok(list
+        .filter(|device| check_sysname(device.sysname()))
+        .map(|device| to_str(device.sysname()))
+        .collect())
+}
+
+fn query(tx: SyncSender<Event>) -> Result<()> {
+    for sysname in list()? {
+        log::debug!("Found device: {sysname}");
+        tx.send(Event::DeviceAdded(sysname))?;
     }
 
     Ok(())
@@ -0,0 +1,316 @@
+use anyhow::{Result, bail};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+//
+// Options
+//
+
+/// Digital button of the controller.
+#[derive(Debug, Deserialize, PartialEq, Eq, Hash, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum Button {
+    DpadUp,
+    DpadRight,
+    DpadDown,
+    DpadLeft,
+    Square,
+    Cross,
+    Circle,
+    Triangle,
+    L1,
+    R1,
+    L2,
+    R2,
+    Create,
+    Options,
+    L3,
+    R3,
+    Ps,
+    Touchpad,
+    Mute,
+}
+
+impl Button {
+    /// Bit of the button in the normalized button mask.
+    /// (Must match the ones in 'dualsense.bpf.c')
+    pub fn mask(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+/// Number of buttons in the normalized button mask.
+pub const BUTTON_COUNT: usize = Button::Mute as usize + 1;
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ButtonOptions {
+    /// Output button of the input buttons.
+    pub remap: HashMap<Button, Button>,
+    /// Buttons that never get reported.
+    pub disabled: Vec<Button>,
+}
+
+impl ButtonOptions {
+    /// Output button masks indexed by the input buttons.
+    pub fn remap_table(&self) -> [u32; BUTTON_COUNT] {
+        let mut table = std::array::from_fn(|index| 1 << index);
+        for (&input, &output) in &self.remap {
+            table[input as usize] = output.mask();
+        }
+        for &button in &self.disabled {
+            table[button as usize] = 0;
+        }
+        table
+    }
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+pub struct TurboOptions {
+    /// Turbo rate in presses per second of the buttons.
+    #[serde(flatten)]
+    pub rates: HashMap<Button, f64>,
+}
+
+/// Smallest turbo rate in presses per second. (Longer periods don't fit the 32-bit nanoseconds)
+const MIN_TURBO_RATE: f64 = 0.5;
+/// Largest turbo rate in presses per second.
+const MAX_TURBO_RATE: f64 = 30.0;
+
+impl TurboOptions {
+    /// Checks that the rates are within the supported range.
+    pub fn validate(&self) -> Result<()> {
+        for (button, &rate) in &self.rates {
+            if !(MIN_TURBO_RATE..=MAX_TURBO_RATE).contains(&rate) {
+                bail!(
+                    "Turbo rate of {button:?} must be between {MIN_TURBO_RATE} and {MAX_TURBO_RATE}! (got {rate})"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Turbo periods in nanoseconds indexed by the buttons. (0 if turbo is off)
+    pub fn period_table(&self) -> [u32; BUTTON_COUNT] {
+        let mut table = [0; BUTTON_COUNT];
+        for (&button, &rate) in &self.rates {
+            if rate > 0.0 {
+                table[button as usize] = (1_000_000_000.0 / rate).round() as u32;
+            }
+        }
+        table
+    }
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ToggleOptions {
+    /// Buttons that latch on the first press and release on the second.
+    pub buttons: Vec<Button>,
+}
+
+impl ToggleOptions {
+    /// Normalized mask of the toggled buttons.
+    pub fn mask(&self) -> u32 {
+        self.buttons
+            .iter()
+            .fold(0, |mask, button| mask | button.mask())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::reference::{Buttons, REPORT_NS};
+
+    #[test]
+    fn check_masks() {
+        // Face buttons and the rest match the bits of the report (D-pad is in place of the hat)
+        assert_eq!(1 << 4, Button::Square.mask());
+        assert_eq!(1 << 7, Button::Triangle.mask());
+        assert_eq!(1 << 8, Button::L1.mask());
+        assert_eq!(1 << 15, Button::R3.mask());
+        assert_eq!(1 << 16, Button::Ps.mask());
+        assert_eq!(1 << 18, Button::Mute.mask());
+        assert_eq!(19, BUTTON_COUNT);
+    }
+
+    #[test]
+    fn check_identity() {
+        let table = ButtonOptions::default().remap_table();
+        for (index, mask) in table.iter().enumerate() {
+            assert_eq!(1 << index, *mask);
+        }
+    }
+
+    #[test]
+    fn check_remap() {
+        let options = ButtonOptions {
+            remap: HashMap::from([
+                (Button::Cross, Button::Circle),
+                (Button::Circle, Button::Cross),
+                (Button::Create, Button::Touchpad),
+            ]),
+            ..Default::default()
+        };
+        let table = options.remap_table();
+
+        assert_eq!(Button::Circle.mask(), table[Button::Cross as usize]);
+        assert_eq!(Button::Cross.mask(), table[Button::Circle as usize]);
+        assert_eq!(Button::Touchpad.mask(), table[Button::Create as usize]);
+        // The touchpad click is still reported by itself
+        assert_eq!(Button::Touchpad.mask(), table[Button::Touchpad as usize]);
+        assert_eq!(Button::Square.mask(), table[Button::Square as usize]);
+    }
+
+    #[test]
+    fn check_disabled() {
+        let options = ButtonOptions {
+            remap: HashMap::from([(Button::Create, Button::Ps)]),
+            disabled: vec![Button::Ps, Button::Touchpad],
+        };
+        let table = options.remap_table();
+
+        assert_eq!(0, table[Button::Ps as usize]);
+        assert_eq!(0, table[Button::Touchpad as usize]);
+        // Other buttons can still be remapped onto the disabled ones
+        assert_eq!(Button::Ps.mask(), table[Button::Create as usize]);
+        assert_eq!(Button::Mute.mask(), table[Button::Mute as usize]);
+    }
+
+    #[test]
+    fn check_deserialize() {
+        let options: ButtonOptions =
+            toml::from_str("remap = { cross = 'circle', dpad-up = 'l1', ps = 'mute' }").unwrap();
+
+        assert_eq!(
+            HashMap::from([
+                (Button::Cross, Button::Circle),
+                (Button::DpadUp, Button::L1),
+                (Button::Ps, Button::Mute),
+            ]),
+            options.remap
+        );
+
+        let options: ButtonOptions = toml::from_str("disabled = ['ps', 'touchpad']").unwrap();
+        assert_eq!(vec![Button::Ps, Button::Touchpad], options.disabled);
+    }
+
+    #[test]
+    fn check_turbo() {
+        let options: TurboOptions = toml::from_str("cross = 10.0\nr2 = 12.5").unwrap();
+        let table = options.period_table();
+
+        assert_eq!(100_000_000, table[Button::Cross as usize]);
+        assert_eq!(80_000_000, table[Button::R2 as usize]);
+        assert_eq!(0, table[Button::Square as usize]);
+        assert_eq!([0; BUTTON_COUNT], TurboOptions::default().period_table());
+
+        // Smallest rate fits the period
+        let options = TurboOptions {
+            rates: HashMap::from([(Button::Cross, MIN_TURBO_RATE)]),
+        };
+        assert_eq!(
+            2_000_000_000,
+            options.period_table()[Button::Cross as usize]
+        );
+    }
+
+    #[test]
+    fn check_turbo_rejected() {
+        let parse = |toml: &str| toml::from_str::<TurboOptions>(toml).unwrap().validate();
+        assert!(parse("cross = 30.0").is_ok());
+        assert!(parse("cross = 0.5").is_ok());
+        assert!(parse("cross = 0.0").is_err());
+        // Period wouldn't fit the 32-bit nanoseconds
+        assert!(parse("cross = 0.2").is_err());
+        assert!(parse("cross = -5.0").is_err());
+        assert!(parse("cross = 30.5").is_err());
+        assert!(parse("cross = nan").is_err());
+    }
+
+    #[test]
+    fn check_toggle() {
+        let options: ToggleOptions = toml::from_str("buttons = ['l3', 'l2', 'l3']").unwrap();
+        assert_eq!(Button::L2.mask() | Button::L3.mask(), options.mask());
+        assert_eq!(0, ToggleOptions::default().mask());
+
+        // Every button fits in the mask
+        let all = ToggleOptions {
+            buttons: vec![Button::DpadUp, Button::Mute],
+        };
+        assert_eq!(1 | 1 << (BUTTON_COUNT - 1), all.mask());
+
+        // Unknown buttons are rejected
+        assert!(toml::from_str::<ToggleOptions>("buttons = ['l4']").is_err());
+    }
+
+    #[test]
+    fn check_toggle_sequence() {
+        let toggle: ToggleOptions = toml::from_str("buttons = ['cross', 'l2']").unwrap();
+        let mut buttons = Buttons::new(&Default::default(), &Default::default(), &toggle);
+        let (cross, square) = (Button::Cross.mask(), Button::Square.mask());
+
+        // Latches on the press and stays latched while held and released
+        let output: Vec<u32> = [cross, cross, 0, 0, cross, cross, 0, 0]
+            .iter()
+            .enumerate()
+            .map(|(i, &input)| buttons.step(input, [0, 0], i as u64 * REPORT_NS).0)
+            .collect();
+        assert_eq!(vec![cross, cross, cross, cross, 0, 0, 0, 0], output);
+
+        // Other buttons pass through
+        assert_eq!(square, buttons.step(square, [0, 0], 0).0);
+    }
+
+    #[test]
+    fn check_toggle_triggers() {
+        let toggle: ToggleOptions = toml::from_str("buttons = ['l2']").unwrap();
+        let mut buttons = Buttons::new(&Default::default(), &Default::default(), &toggle);
+        let l2 = Button::L2.mask();
+
+        // Analog trigger latches fully pressed with its button
+        assert_eq!((l2, [0xFF, 40]), buttons.step(l2, [80, 40], 0));
+        assert_eq!((l2, [0xFF, 40]), buttons.step(0, [0, 40], REPORT_NS));
+
+        // and releases fully on the next press
+        assert_eq!((0, [0, 40]), buttons.step(l2, [200, 40], 2 * REPORT_NS));
+        assert_eq!((0, [0, 40]), buttons.step(0, [0, 40], 3 * REPORT_NS));
+    }
+
+    #[test]
+    fn check_toggle_remap() {
+        // Toggles apply to the output of the remap
+        let remap: ButtonOptions = toml::from_str("remap = { cross = 'circle' }").unwrap();
+        let toggle: ToggleOptions = toml::from_str("buttons = ['circle']").unwrap();
+        let mut buttons = Buttons::new(&remap, &Default::default(), &toggle);
+        let (cross, circle) = (Button::Cross.mask(), Button::Circle.mask());
+
+        assert_eq!(circle, buttons.step(cross, [0, 0], 0).0);
+        assert_eq!(circle, buttons.step(0, [0, 0], REPORT_NS).0);
+        assert_eq!(0, buttons.step(cross, [0, 0], 2 * REPORT_NS).0);
+
+        // The input button isn't toggled anymore
+        let toggle: ToggleOptions = toml::from_str("buttons = ['cross']").unwrap();
+        let mut buttons = Buttons::new(&remap, &Default::default(), &toggle);
+        assert_eq!(circle, buttons.step(cross, [0, 0], 0).0);
+        assert_eq!(0, buttons.step(0, [0, 0], REPORT_NS).0);
+    }
+
+    #[test]
+    fn check_toggle_turbo() {
+        // Latched button repeats until toggled off
+        let turbo: TurboOptions = toml::from_str("r2 = 10.0").unwrap();
+        let toggle: ToggleOptions = toml::from_str("buttons = ['r2']").unwrap();
+        let mut buttons = Buttons::new(&Default::default(), &turbo, &toggle);
+        let r2 = Button::R2.mask();
+
+        let ms = 1_000_000;
+        assert_eq!((r2, [0, 0xFF]), buttons.step(r2, [0, 90], ms));
+        assert_eq!((r2, [0, 0xFF]), buttons.step(0, [0, 0], 40 * ms));
+        assert_eq!((0, [0, 0]), buttons.step(0, [0, 0], 60 * ms));
+        assert_eq!((r2, [0, 0xFF]), buttons.step(0, [0, 0], 110 * ms));
+        assert_eq!((0, [0, 0]), buttons.step(r2, [0, 90], 120 * ms));
+        assert_eq!((0, [0, 0]), buttons.step(0, [0, 0], 160 * ms));
+    }
+}
@@ -0,0 +1,228 @@
+use anyhow::{Result, bail};
+use serde::Deserialize;
+
+/// Response curve mapping the 0.0 to 1.0 range onto itself.
+#[derive(Debug, Default, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub enum Curve {
+    /// Output is the same as the input.
+    #[default]
+    Linear,
+    /// Raises the input to the given power.
+    Power(f64),
+    /// Piecewise-linear interpolation between the control points.
+    Points(Vec<[f64; 2]>),
+    /// Cubic Bezier from (0, 0) to (1, 1) with 2 control points (x1, y1, x2, y2).
+    Bezier([f64; 4]),
+}
+
+impl Curve {
+    /// Checks that the curve stays in the 0.0 to 1.0 range and doesn't go backwards.
+    pub fn validate(&self) -> Result<()> {
+        let in_range = |value: f64| (0.0..=1.0).contains(&value);
+
+        match self {
+            Curve::Linear => (),
+            Curve::Power(exponent) => {
+                if !(*exponent > 0.0 && exponent.is_finite()) {
+                    bail!("Curve exponent must be more than 0! (got {exponent})");
+                }
+            }
+            Curve::Points(points) => {
+                if !points.iter().flatten().all(|&value| in_range(value)) {
+                    bail!("Curve points must be between 0 and 1! (got {points:?})");
+                }
+                // Points with the same X would make a vertical step
+                let sorted = points
+                    .windows(2)
+                    .all(|w| w[0][0] < w[1][0] && w[0][1] <= w[1][1]);
+                if !sorted {
+                    bail!("Curve points must be sorted without duplicates! (got {points:?})");
+                }
+            }
+            Curve::Bezier(points) => {
+                if !points.iter().all(|&value| in_range(value)) {
+                    bail!("Curve control points must be between 0 and 1! (got {points:?})");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the curve to the value. Values outside of the 0.0 to 1.0 range are left as is.
+    pub fn apply(&self, value: f64) -> f64 {
+        if !(0.0..1.0).contains(&value) {
+            return value;
+        }
+
+        match self {
+            Curve::Linear => value,
+            Curve::Power(exponent) => value.powf(*exponent),
+            Curve::Points(points) => piecewise(points, value),
+            Curve::Bezier([x1, y1, x2, y2]) => bezier(*x1, *y1, *x2, *y2, value),
+        }
+    }
+}
+
+/// Linear interpolation between the points with implicit (0, 0) and (1, 1) ends.
+fn piecewise(points: &[[f64; 2]], x: f64) -> f64 {
+    let mut prev = [0.0, 0.0];
+    for &next in points.iter().chain(std::iter::once(&[1.0, 1.0])) {
+        if x <= next[0] {
+            let width = next[0] - prev[0];
+            if width <= 0.0 {
+                return next[1];
+            }
+            let t = (x - prev[0]) / width;
+            return prev[1] + (next[1] - prev[1]) * t;
+        }
+        prev = next;
+    }
+    x
+}
+
+fn bezier(x1: f64, y1: f64, x2: f64, y2: f64, x: f64) -> f64 {
+    // Keep the curve a function of x
+    let x1 = x1.clamp(0.0, 1.0);
+    let x2 = x2.clamp(0.0, 1.0);
+
+    // Find the curve parameter for x with bisection
+    let (mut low, mut high) = (0.0, 1.0);
+    for _ in 0..64 {
+        let t = (low + high) / 2.0;
+        if cubic(x1, x2, t) < x {
+            low = t;
+        } else {
+            high = t;
+        }
+    }
+
+    cubic(y1, y2, (low + high) / 2.0)
+}
+
+/// One dimension of a cubic Bezier with the ends at 0.0 and 1.0.
+fn cubic(p1: f64, p2: f64, t: f64) -> f64 {
+    let u = 1.0 - t;
+    3.0 * u * u * t * p1 + 3.0 * u * t * t * p2 + t * t * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(expected: f64, actual: f64) {
+        assert!(
+            (expected - actual).abs() < 1e-6,
+            "expected = {expected}, actual = {actual}"
+        );
+    }
+
+    fn check_ends(curve: Curve) {
+        assert_near(0.0, curve.apply(0.0));
+        assert_near(1.0, curve.apply(1.0));
+        assert_near(1.2, curve.apply(1.2));
+    }
+
+    #[test]
+    fn check_linear() {
+        check_ends(Curve::Linear);
+        assert_near(0.3, Curve::Linear.apply(0.3));
+    }
+
+    #[test]
+    fn check_power() {
+        check_ends(Curve::Power(2.0));
+        assert_near(0.25, Curve::Power(2.0).apply(0.5));
+        assert_near(0.5, Curve::Power(0.5).apply(0.25));
+    }
+
+    #[test]
+    fn check_points() {
+        let curve = Curve::Points(vec![[0.5, 0.2], [0.8, 0.5]]);
+        check_ends(curve.clone());
+        assert_near(0.1, curve.apply(0.25));
+        assert_near(0.2, curve.apply(0.5));
+        assert_near(0.35, curve.apply(0.65));
+        assert_near(0.75, curve.apply(0.9));
+    }
+
+    #[test]
+    fn check_bezier() {
+        check_ends(Curve::Bezier([0.4, 0.0, 0.6, 1.0]));
+
+        // Control points on the diagonal are linear
+        let linear = Curve::Bezier([0.25, 0.25, 0.75, 0.75]);
+        for x in [0.1, 0.3, 0.5, 0.9] {
+            assert_near(x, linear.apply(x));
+        }
+
+        // Symmetric ease-in-out
+        let curve = Curve::Bezier([0.5, 0.0, 0.5, 1.0]);
+        assert_near(0.5, curve.apply(0.5));
+        assert!(curve.apply(0.25) < 0.25);
+        assert!(curve.apply(0.75) > 0.75);
+    }
+
+    #[test]
+    fn check_rejected() {
+        assert!(Curve::Linear.validate().is_ok());
+        assert!(Curve::Power(0.5).validate().is_ok());
+        assert!(
+            Curve::Points(vec![[0.0, 0.0], [0.5, 0.5], [1.0, 1.0]])
+                .validate()
+                .is_ok()
+        );
+        assert!(Curve::Bezier([0.0, 1.0, 1.0, 0.0]).validate().is_ok());
+
+        // Resting trigger would read as fully pressed
+        assert!(Curve::Power(0.0).validate().is_err());
+        assert!(Curve::Power(-1.0).validate().is_err());
+        assert!(Curve::Power(f64::NAN).validate().is_err());
+        assert!(Curve::Power(f64::INFINITY).validate().is_err());
+
+        assert!(
+            Curve::Points(vec![[0.8, 0.5], [0.5, 0.2]])
+                .validate()
+                .is_err()
+        );
+        assert!(
+            Curve::Points(vec![[0.5, 0.2], [0.5, 0.8]])
+                .validate()
+                .is_err()
+        );
+        assert!(
+            Curve::Points(vec![[0.2, 0.5], [0.5, 0.2]])
+                .validate()
+                .is_err()
+        );
+        assert!(Curve::Points(vec![[0.5, 1.2]]).validate().is_err());
+        assert!(Curve::Points(vec![[-0.1, 0.0]]).validate().is_err());
+        assert!(Curve::Points(vec![[f64::NAN, 0.5]]).validate().is_err());
+
+        assert!(Curve::Bezier([0.4, -0.5, 0.6, 1.0]).validate().is_err());
+        assert!(Curve::Bezier([1.5, 0.0, 0.6, 1.0]).validate().is_err());
+    }
+
+    #[test]
+    fn check_deserialize() {
+        #[derive(Deserialize)]
+        struct Options {
+            curve: Curve,
+        }
+
+        fn parse(toml: &str) -> Curve {
+            toml::from_str::<Options>(toml).unwrap().curve
+        }
+
+        assert_eq!(Curve::Linear, parse("curve = 'linear'"));
+        assert_eq!(Curve::Power(1.5), parse("curve = { power = 1.5 }"));
+        assert_eq!(
+            Curve::Points(vec![[0.5, 0.25]]),
+            parse("curve = { points = [[0.5, 0.25]] }")
+        );
+        assert_eq!(
+            Curve::Bezier([0.4, 0.0, 0.6, 1.0]),
+            parse("curve = { bezier = [0.4, 0.0, 0.6, 1.0] }")
+        );
+    }
+}
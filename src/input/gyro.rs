@@ -0,0 +1,371 @@
+use super::buttons::Button;
+use super::util::fields_to_ne_bytes;
+use anyhow::{Result, bail};
+use serde::Deserialize;
+
+//
+// Options
+//
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct GyroOptions {
+    /// Add the gyro speed to the right stick.
+    pub enabled: bool,
+    /// Stick deflection per 100 degrees per second. (X, Y)
+    pub sensitivity: [f64; 2],
+    /// Speeds below this many degrees per second are ignored.
+    pub deadzone: f64,
+    /// Inverts each axis. (X, Y)
+    pub invert: [bool; 2],
+    /// Button that has to be held for the gyro to be enabled.
+    pub button: Option<Button>,
+}
+
+impl Default for GyroOptions {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sensitivity: [0.5, 0.5],
+            deadzone: 0.0,
+            invert: [false, false],
+            button: None,
+        }
+    }
+}
+
+/// Largest sensitivity in stick deflections per 100 degrees per second.
+const MAX_SENSITIVITY: f64 = 100.0;
+/// Largest deadzone in degrees per second. (Range of the gyro)
+const MAX_DEADZONE: f64 = 2000.0;
+
+impl GyroOptions {
+    /// Checks that the sensitivity and deadzone are within the supported range.
+    pub fn validate(&self) -> Result<()> {
+        for sensitivity in self.sensitivity {
+            if !(0.0..=MAX_SENSITIVITY).contains(&sensitivity) {
+                bail!(
+                    "Gyro sensitivity must be between 0 and {MAX_SENSITIVITY}! (got {sensitivity})"
+                );
+            }
+        }
+        if !(0.0..=MAX_DEADZONE).contains(&self.deadzone) {
+            bail!(
+                "Gyro deadzone must be between 0 and {MAX_DEADZONE} deg/s! (got {})",
+                self.deadzone
+            );
+        }
+        Ok(())
+    }
+
+    pub fn params(&self) -> GyroParams {
+        GyroParams::new(self)
+    }
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Clone)]
+#[serde(default)]
+pub struct GyroCalibration {
+    /// Raw speed of each axis at rest. (X, Y, Z)
+    pub offset: [f64; 3],
+}
+
+impl GyroCalibration {
+    /// Offsets rounded to raw units in the format used by the eBPF program.
+    pub fn to_ne_bytes(&self) -> [u8; 12] {
+        let mut bytes = [0; 12];
+        for (chunk, offset) in bytes.chunks_exact_mut(4).zip(self.offset) {
+            chunk.copy_from_slice(&(offset.round() as i32).to_ne_bytes());
+        }
+        bytes
+    }
+}
+
+//
+// Parameters
+//
+
+/// Raw gyro units per degree per second. (+-2000 deg/s over the 16-bit range)
+pub const GYRO_RAW_PER_DEG_S: f64 = 16.384;
+
+/// Raw stick units of full deflection.
+const STICK_RAW_RANGE: f64 = u8::MAX as f64 / 2.0;
+
+/// Gyro aiming parameters in the fixed-point format used by the eBPF program.
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct GyroParams {
+    pub enabled: u32,
+    /// Raw stick units per raw gyro unit of the X (yaw) and Y (pitch) axes. (Q16)
+    pub scale: [i32; 2],
+    /// Speeds below this are ignored. (Raw gyro units)
+    pub deadzone: u32,
+    /// Button mask that has to be held for the gyro to be enabled. (0 if always enabled)
+    pub button: u32,
+}
+
+impl GyroParams {
+    pub fn new(options: &GyroOptions) -> Self {
+        // Turning left and tilting up give positive speeds while the stick grows to the right and down
+        let scale = |sensitivity: f64, invert: bool| {
+            let scale = -sensitivity / 100.0 * STICK_RAW_RANGE / GYRO_RAW_PER_DEG_S;
+            let scale = if invert { -scale } else { scale };
+            (scale * 65536.0).round() as i32
+        };
+
+        Self {
+            enabled: options.enabled as u32,
+            scale: [
+                scale(options.sensitivity[0], options.invert[0]),
+                scale(options.sensitivity[1], options.invert[1]),
+            ],
+            deadzone: (options.deadzone.max(0.0) * GYRO_RAW_PER_DEG_S).round() as u32,
+            button: options.button.map_or(0, Button::mask),
+        }
+    }
+
+    pub fn to_ne_bytes(self) -> [u8; 20] {
+        fields_to_ne_bytes(&[
+            self.enabled,
+            self.scale[0] as u32,
+            self.scale[1] as u32,
+            self.deadzone,
+            self.button,
+        ])
+    }
+}
+
+//
+// Bias Calibration
+//
+
+const REPORT_USB: u8 = 0x01;
+const REPORT_USB_SIZE: usize = 64;
+const REPORT_BT: u8 = 0x31;
+const REPORT_BT_SIZE: usize = 78;
+/// First byte of the CRC of the Bluetooth reports. (Not part of the report)
+const REPORT_CRC_SEED: u8 = 0xA1;
+/// Offset of the gyro in the input report excluding the USB/BT headers.
+const REPORT_GYRO_OFFSET: usize = 15;
+
+/// Speeds that differ more than this many degrees per second mean the controller was moved.
+const MAX_REST_NOISE: f64 = 5.0;
+
+/// Reads the raw gyro speeds (X, Y, Z) from the USB or Bluetooth input report.
+/// (None if it's a different or corrupted report)
+pub fn parse_gyro(report: &[u8]) -> Option<[i16; 3]> {
+    let start = match (report.first()?, report.len()) {
+        (&REPORT_USB, REPORT_USB_SIZE) => 1,
+        (&REPORT_BT, REPORT_BT_SIZE) if check_crc(report) => 2,
+        _ => return None,
+    } + REPORT_GYRO_OFFSET;
+
+    let axis = |i: usize| i16::from_le_bytes([report[start + i * 2], report[start + i * 2 + 1]]);
+    Some([axis(0), axis(1), axis(2)])
+}
+
+/// Checks the CRC at the end of the Bluetooth report like in 'dualsense.bpf.c'.
+fn check_crc(report: &[u8]) -> bool {
+    let (data, crc) = report.split_at(report.len() - 4);
+    let crc = u32::from_le_bytes(crc.try_into().unwrap());
+    !crc32(crc32(!0, &[REPORT_CRC_SEED]), data) == crc
+}
+
+/// CRC-32 (IEEE) without the final inversion.
+fn crc32(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xEDB8_8320,
+                _ => crc >> 1,
+            };
+        }
+    }
+    crc
+}
+
+/// Collects the gyro speeds of a controller at rest.
+#[derive(Debug, Default)]
+pub struct GyroBias {
+    count: u32,
+    sum: [i64; 3],
+    min: [i16; 3],
+    max: [i16; 3],
+}
+
+impl GyroBias {
+    pub fn add(&mut self, speeds: [i16; 3]) {
+        for (i, speed) in speeds.into_iter().enumerate() {
+            if self.count == 0 {
+                self.min[i] = speed;
+                self.max[i] = speed;
+            }
+            self.sum[i] += speed as i64;
+            self.min[i] = self.min[i].min(speed);
+            self.max[i] = self.max[i].max(speed);
+        }
+        self.count += 1;
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Returns true if the speeds varied too much for the controller to have been at rest.
+    pub fn moved(&self) -> bool {
+        let limit = MAX_REST_NOISE * GYRO_RAW_PER_DEG_S;
+        (0..3).any(|i| (self.max[i] as f64 - self.min[i] as f64) > limit)
+    }
+
+    /// Average speeds of the samples rounded to raw units. (None if there are none)
+    pub fn calibration(&self) -> Option<GyroCalibration> {
+        (self.count > 0).then(|| GyroCalibration {
+            offset: self.sum.map(|sum| (sum as f64 / self.count as f64).round()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::reference::gyro_offset as offset;
+
+    fn speed(deg_s: f64) -> i16 {
+        (deg_s * GYRO_RAW_PER_DEG_S).round() as i16
+    }
+
+    #[test]
+    fn check_sensitivity() {
+        let params = GyroOptions {
+            enabled: true,
+            sensitivity: [1.0, 0.5],
+            ..Default::default()
+        }
+        .params();
+
+        assert_eq!(1, params.enabled);
+        // Full deflection at 100 deg/s
+        assert_eq!(-128, offset(&params, 0, speed(100.0)));
+        assert_eq!(127, offset(&params, 0, speed(-100.0)));
+        // Half deflection at 100 deg/s
+        assert_eq!(-64, offset(&params, 1, speed(100.0)));
+    }
+
+    #[test]
+    fn check_invert_and_deadzone() {
+        let params = GyroOptions {
+            sensitivity: [1.0, 1.0],
+            deadzone: 2.0,
+            invert: [true, false],
+            ..Default::default()
+        }
+        .params();
+
+        assert_eq!(0, params.enabled);
+        assert_eq!(33, params.deadzone);
+        assert_eq!(0, offset(&params, 0, speed(1.9)));
+        assert!(offset(&params, 0, speed(10.0)) > 0);
+        assert!(offset(&params, 1, speed(10.0)) < 0);
+    }
+
+    #[test]
+    fn check_button() {
+        let params = GyroOptions::default().params();
+        assert_eq!(0, params.button);
+
+        let options: GyroOptions = toml::from_str("button = 'l2'").unwrap();
+        assert_eq!(Button::L2.mask(), options.params().button);
+    }
+
+    #[test]
+    fn check_rejected() {
+        let parse = |toml: &str| toml::from_str::<GyroOptions>(toml).unwrap().validate();
+        assert!(parse("sensitivity = [0.0, 100.0]\ndeadzone = 2000.0").is_ok());
+        assert!(parse("sensitivity = [-0.5, 0.5]").is_err());
+        assert!(parse("sensitivity = [0.5, 101.0]").is_err());
+        assert!(parse("sensitivity = [nan, 0.5]").is_err());
+        assert!(parse("deadzone = -1.0").is_err());
+        assert!(parse("deadzone = 2001.0").is_err());
+    }
+
+    /// Bluetooth report with the gyro speeds and a valid CRC.
+    fn bt_report(gyro: [u8; 6]) -> [u8; 78] {
+        let mut bt = [0u8; 78];
+        bt[0] = REPORT_BT;
+        bt[17..23].copy_from_slice(&gyro);
+        let crc = !crc32(crc32(!0, &[REPORT_CRC_SEED]), &bt[..74]);
+        bt[74..].copy_from_slice(&crc.to_le_bytes());
+        bt
+    }
+
+    #[test]
+    fn check_crc32() {
+        assert_eq!(0xCBF4_3926, !crc32(!0, b"123456789"));
+    }
+
+    #[test]
+    fn check_parse_gyro() {
+        let mut usb = [0u8; 64];
+        usb[0] = REPORT_USB;
+        usb[16..22].copy_from_slice(&[0x01, 0x00, 0xFF, 0xFF, 0x00, 0x80]);
+        assert_eq!(Some([1, -1, i16::MIN]), parse_gyro(&usb));
+
+        let bt = bt_report([0x10, 0x00, 0x20, 0x00, 0x30, 0x00]);
+        assert_eq!(Some([16, 32, 48]), parse_gyro(&bt));
+
+        // Wrong ID or size
+        assert_eq!(None, parse_gyro(&[0x02; 64]));
+        assert_eq!(None, parse_gyro(&bt[..64]));
+        assert_eq!(None, parse_gyro(&[REPORT_USB; 65]));
+        assert_eq!(None, parse_gyro(&[]));
+    }
+
+    #[test]
+    fn check_parse_corrupted() {
+        // Any changed byte breaks the CRC
+        for index in [1, 17, 22, 73, 74, 77] {
+            let mut bt = bt_report([0x10, 0x00, 0x20, 0x00, 0x30, 0x00]);
+            bt[index] ^= 0x04;
+            assert_eq!(None, parse_gyro(&bt), "index = {index}");
+        }
+    }
+
+    #[test]
+    fn check_bias() {
+        let mut bias = GyroBias::default();
+        assert_eq!(None, bias.calibration());
+
+        bias.add([10, -4, 0]);
+        bias.add([12, -6, 1]);
+        bias.add([11, -5, 2]);
+        assert_eq!(3, bias.count());
+        assert!(!bias.moved());
+        assert_eq!([11.0, -5.0, 1.0], bias.calibration().unwrap().offset);
+
+        // Turning the controller during calibration
+        bias.add([500, -5, 1]);
+        assert!(bias.moved());
+    }
+
+    #[test]
+    fn check_bias_rounding() {
+        let mut bias = GyroBias::default();
+        bias.add([2, -5, 1]);
+        bias.add([3, -6, 0]);
+        bias.add([3, -6, 0]);
+
+        // Averages of 2.67, -5.67 and 0.33
+        assert_eq!([3.0, -6.0, 0.0], bias.calibration().unwrap().offset);
+    }
+
+    #[test]
+    fn check_calibration_bytes() {
+        let bytes = GyroCalibration {
+            offset: [1.4, -2.6, 0.0],
+        }
+        .to_ne_bytes();
+        let value = |i: usize| i32::from_ne_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap());
+
+        assert_eq!([1, -3, 0], [value(0), value(1), value(2)]);
+    }
+}
@@ -1,6 +1,18 @@
+mod buttons;
+mod curve;
+mod gyro;
+mod motion;
+#[cfg(test)]
+mod reference;
+mod smoothing;
 mod stick;
+mod touchpad;
 mod trigger;
 mod util;
 
-pub use stick::StickOptions;
+pub use buttons::{ButtonOptions, ToggleOptions, TurboOptions};
+pub use gyro::{GyroBias, GyroCalibration, GyroOptions, parse_gyro};
+pub use motion::MotionOptions;
+pub use stick::{StickCalibration, StickOptions};
+pub use touchpad::TouchpadOptions;
 pub use trigger::TriggerOptions;
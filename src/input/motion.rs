@@ -0,0 +1,277 @@
+use super::util::fields_to_ne_bytes;
+use anyhow::{Result, bail};
+use serde::Deserialize;
+use std::f64::consts::TAU;
+
+//
+// Options
+//
+
+#[derive(Debug, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct MotionOptions {
+    pub gyro: SensorOptions,
+    pub accel: SensorOptions,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct SensorOptions {
+    /// Source axis of each axis. (X, Y, Z)
+    pub axes: [Axis; 3],
+    /// Inverts each axis. (X, Y, Z)
+    pub invert: [bool; 3],
+    /// Scale of each axis. (X, Y, Z)
+    pub scale: [f64; 3],
+    /// Cutoff frequency in Hz of the low-pass filter.
+    pub low_pass: Option<f64>,
+}
+
+impl Default for SensorOptions {
+    fn default() -> Self {
+        Self {
+            axes: [Axis::X, Axis::Y, Axis::Z],
+            invert: [false, false, false],
+            scale: [1.0, 1.0, 1.0],
+            low_pass: None,
+        }
+    }
+}
+
+/// Largest scale of an axis.
+const MAX_SCALE: f64 = 16.0;
+/// Largest cutoff frequency in Hz of the low-pass filter.
+const MAX_LOW_PASS: f64 = 1000.0;
+
+impl MotionOptions {
+    /// Checks the options of both sensors.
+    pub fn validate(&self) -> Result<()> {
+        self.gyro.validate("Gyro")?;
+        self.accel.validate("Accelerometer")
+    }
+}
+
+impl SensorOptions {
+    /// Checks that the scales and low-pass cutoff of the named sensor are in range.
+    pub fn validate(&self, name: &str) -> Result<()> {
+        for scale in self.scale {
+            if !(0.0..=MAX_SCALE).contains(&scale) {
+                bail!("{name} scale must be between 0 and {MAX_SCALE}! (got {scale})");
+            }
+        }
+        #[allow(clippy::collapsible_if)]
+        if let Some(hz) = self.low_pass {
+            if !(hz > 0.0 && hz <= MAX_LOW_PASS) {
+                bail!(
+                    "{name} low-pass cutoff must be more than 0 and at most {MAX_LOW_PASS} Hz! (got {hz})"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn params(&self) -> SensorParams {
+        SensorParams::new(self)
+    }
+}
+
+//
+// Parameters
+//
+
+/// Motion sensor parameters in the fixed-point format used by the eBPF program.
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct SensorParams {
+    /// Source axis index of each axis.
+    pub axes: [u32; 3],
+    /// Scale of each axis including the inversion. (Q16)
+    pub scale: [i32; 3],
+    /// Cutoff of the low-pass filter in rad/s. (Q16, 0 if disabled)
+    pub cutoff: u32,
+}
+
+impl SensorParams {
+    pub fn new(options: &SensorOptions) -> Self {
+        let scale = |i: usize| {
+            let scale = match options.invert[i] {
+                true => -options.scale[i],
+                false => options.scale[i],
+            };
+            (scale * 65536.0).round() as i32
+        };
+
+        Self {
+            axes: options.axes.map(|axis| axis as u32),
+            scale: [scale(0), scale(1), scale(2)],
+            cutoff: options
+                .low_pass
+                .map_or(0, |hz| (TAU * hz * 65536.0).round() as u32),
+        }
+    }
+
+    pub fn to_ne_bytes(self) -> [u8; 28] {
+        fields_to_ne_bytes(&[
+            self.axes[0],
+            self.axes[1],
+            self.axes[2],
+            self.scale[0] as u32,
+            self.scale[1] as u32,
+            self.scale[2] as u32,
+            self.cutoff,
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::reference::{REPORT_NS, Sensor};
+
+    #[test]
+    fn check_identity() {
+        let params = SensorOptions::default().params();
+        assert_eq!([0, 1, 2], params.axes);
+        assert_eq!([65536; 3], params.scale);
+        assert_eq!(0, params.cutoff);
+    }
+
+    #[test]
+    fn check_remap() {
+        // Controller held flat swaps yaw and roll
+        let options: SensorOptions =
+            toml::from_str("axes = ['x', 'z', 'y']\ninvert = [false, false, true]").unwrap();
+        let params = options.params();
+
+        assert_eq!([0, 2, 1], params.axes);
+        assert_eq!([65536, 65536, -65536], params.scale);
+    }
+
+    #[test]
+    fn check_scale() {
+        let params = SensorOptions {
+            scale: [2.0, 0.5, 0.0],
+            invert: [false, true, false],
+            ..Default::default()
+        }
+        .params();
+        assert_eq!([131072, -32768, 0], params.scale);
+
+        // Largest scale stays in range when inverted
+        let params = SensorOptions {
+            scale: [MAX_SCALE; 3],
+            invert: [true; 3],
+            ..Default::default()
+        }
+        .params();
+        assert_eq!(-(16 << 16), params.scale[0]);
+    }
+
+    #[test]
+    fn check_rejected() {
+        let parse = |toml: &str| toml::from_str::<MotionOptions>(toml).unwrap().validate();
+        assert!(parse("gyro = { scale = [0.0, 1.0, 16.0], low_pass = 1000.0 }").is_ok());
+        assert!(parse("gyro = { scale = [-1.0, 1.0, 1.0] }").is_err());
+        assert!(parse("accel = { scale = [1.0, 16.5, 1.0] }").is_err());
+        assert!(parse("accel = { scale = [1.0, 1.0, nan] }").is_err());
+        assert!(parse("gyro = { low_pass = 0.0 }").is_err());
+        assert!(parse("accel = { low_pass = 1001.0 }").is_err());
+    }
+
+    #[test]
+    fn check_low_pass() {
+        let params = SensorOptions {
+            low_pass: Some(10.0),
+            ..Default::default()
+        }
+        .params();
+        assert_eq!((TAU * 10.0 * 65536.0).round() as u32, params.cutoff);
+
+        // Largest cutoff fits in the field
+        let params = SensorOptions {
+            low_pass: Some(MAX_LOW_PASS),
+            ..Default::default()
+        }
+        .params();
+        assert!(params.cutoff > 0 && params.cutoff < u32::MAX, "{params:?}");
+    }
+
+    #[test]
+    fn check_params_bytes() {
+        let bytes = SensorParams {
+            axes: [2, 0, 1],
+            scale: [65536, -65536, 3],
+            cutoff: 7,
+        }
+        .to_ne_bytes();
+        assert_eq!(2u32.to_ne_bytes(), bytes[0..4]);
+        assert_eq!((-65536i32).to_ne_bytes(), bytes[16..20]);
+        assert_eq!(7u32.to_ne_bytes(), bytes[24..28]);
+    }
+
+    #[test]
+    fn check_transform() {
+        let mut sensor = Sensor::new(SensorOptions::default().params());
+        for input in [[0, 0, 0], [100, -200, 300], [i16::MIN, i16::MAX, -1]] {
+            assert_eq!(input, sensor.step(input, REPORT_NS));
+        }
+
+        let options: SensorOptions =
+            toml::from_str("axes = ['x', 'z', 'y']\ninvert = [false, false, true]").unwrap();
+        let mut sensor = Sensor::new(options.params());
+        assert_eq!([1, 3, -2], sensor.step([1, 2, 3], REPORT_NS));
+        // Inverting the minimum doesn't overflow
+        assert_eq!([0, 0, i16::MAX], sensor.step([0, i16::MIN, 0], REPORT_NS));
+
+        let mut sensor = Sensor::new(
+            SensorOptions {
+                scale: [2.0, 0.5, 1.5],
+                ..Default::default()
+            }
+            .params(),
+        );
+        assert_eq!([200, 50, 150], sensor.step([100, 100, 100], REPORT_NS));
+        // Clamped to the 16-bit range
+        assert_eq!(
+            [i16::MAX, 10000, i16::MIN],
+            sensor.step([20000, 20000, -30000], REPORT_NS)
+        );
+    }
+
+    #[test]
+    fn check_low_pass_output() {
+        let mut sensor = Sensor::new(
+            SensorOptions {
+                low_pass: Some(10.0),
+                ..Default::default()
+            }
+            .params(),
+        );
+
+        // First sample passes through
+        let mut now = REPORT_NS;
+        assert_eq!([1000, 0, 0], sensor.step([1000, 0, 0], now));
+
+        // Step response reaches ~63% after the time constant (1 / (2 * PI * 10 Hz) = 16 ms)
+        let mut output = [0; 3];
+        for _ in 0..4 {
+            now += REPORT_NS;
+            output = sensor.step([2000, 0, 0], now);
+        }
+        assert!((1550..1700).contains(&output[0]), "{output:?}");
+
+        // Converges to the input
+        for _ in 0..100 {
+            now += REPORT_NS;
+            output = sensor.step([2000, 0, 0], now);
+        }
+        assert_eq!([2000, 0, 0], output);
+    }
+}
@@ -0,0 +1,333 @@
+//! Reference implementations of the eBPF program's filters used by the unit tests.
+//! (Must match the ones in 'dualsense.bpf.c')
+
+use super::buttons::{BUTTON_COUNT, Button, ButtonOptions, ToggleOptions, TurboOptions};
+use super::gyro::GyroParams;
+use super::motion::SensorParams;
+use super::smoothing::{MODE_AVERAGE, MODE_EMA, MODE_ONE_EURO, SmoothingParams};
+use super::touchpad::TouchRegionParams;
+
+pub const NSEC_PER_SEC: u64 = 1_000_000_000;
+pub const MIN_DELTA_TIME: u64 = 100_000;
+/// Report interval over Bluetooth.
+pub const REPORT_NS: u64 = 4_000_000;
+
+/// Time elapsed since the last report clamped like in the eBPF program.
+fn delta_time(time: &mut u64, now: u64) -> u64 {
+    let dt = now.wrapping_sub(*time).clamp(MIN_DELTA_TIME, NSEC_PER_SEC);
+    *time = now;
+    dt
+}
+
+/// Smoothing factor (Q16) of a low-pass filter with the cutoff (rad/s, Q16) over the elapsed time.
+fn cutoff_alpha(cutoff: u64, dt: u64) -> u64 {
+    let wt = cutoff * dt / NSEC_PER_SEC;
+    (wt << 16) / ((1 << 16) + wt)
+}
+
+//
+// Smoothing
+//
+
+/// Smoothing of a single axis. ('apply_smoothing')
+pub struct Smoothing {
+    params: SmoothingParams,
+    ready: bool,
+    time: u64,
+    index: u32,
+    count: u32,
+    times: [u64; 256],
+    arr: [u8; 256],
+    sum: u32,
+    value: i64,
+    delta: i64,
+}
+
+impl Smoothing {
+    pub fn new(params: SmoothingParams) -> Self {
+        Self {
+            params,
+            ready: false,
+            time: 0,
+            index: 0,
+            count: 0,
+            times: [0; 256],
+            arr: [0; 256],
+            sum: 0,
+            value: 0,
+            delta: 0,
+        }
+    }
+
+    pub fn step(&mut self, input: u8, now: u64) -> u8 {
+        let target = (input as i64) << 16;
+        let dt = delta_time(&mut self.time, now);
+
+        match self.params.mode {
+            MODE_AVERAGE if self.params.time > 0 => return self.timed_average(input, now),
+            MODE_AVERAGE => return self.average(input),
+            _ if !self.ready => {
+                // Start the filters from the first input
+                self.ready = true;
+                self.value = target;
+            }
+            MODE_EMA => {
+                // Time constant of half the window has the same center of mass as the average
+                let alpha = match self.params.time {
+                    0 => self.params.alpha as u64,
+                    time => (dt << 16) / (time as u64 / 2 + dt),
+                };
+                self.value += ((target - self.value) * alpha as i64) >> 16;
+            }
+            MODE_ONE_EURO => {
+                // Speed of the input, filtered to reduce jitter
+                let diff = target - self.value;
+                let mut delta = (diff.unsigned_abs() * NSEC_PER_SEC / dt) as i64;
+                if diff < 0 {
+                    delta = -delta;
+                }
+                let alpha = cutoff_alpha(self.params.d_cutoff as u64, dt) as i64;
+                self.delta += ((delta - self.delta) * alpha) >> 16;
+
+                // Raise the cutoff with speed to reduce lag
+                let speed = self.delta.unsigned_abs();
+                let cutoff =
+                    self.params.min_cutoff as u64 + ((self.params.beta as u64 * speed) >> 16);
+                let alpha = cutoff_alpha(cutoff.min(u32::MAX as u64), dt) as i64;
+                self.value += (diff * alpha) >> 16;
+            }
+            _ => return input,
+        }
+
+        ((self.value + 0x8000) >> 16) as u8
+    }
+
+    fn average(&mut self, input: u8) -> u8 {
+        let window = self.params.window;
+        if window < 2 {
+            return input;
+        }
+
+        let index = self.index as usize;
+        self.sum -= self.arr[index] as u32;
+        self.arr[index] = input;
+        self.sum += input as u32;
+
+        self.index += 1;
+        self.count = self.count.max(self.index);
+        if self.index > window {
+            self.index = 0;
+        }
+        (self.sum / self.count) as u8
+    }
+
+    fn timed_average(&mut self, input: u8, now: u64) -> u8 {
+        let tail = |s: &Self| (s.index.wrapping_sub(s.count) & 0xFF) as usize;
+
+        // Make room for the new sample if needed
+        if self.count >= 256 {
+            self.sum -= self.arr[tail(self)] as u32;
+            self.count -= 1;
+        }
+
+        let head = (self.index & 0xFF) as usize;
+        self.arr[head] = input;
+        self.sum += input as u32;
+        self.times[head] = now;
+        self.index = (head as u32 + 1) & 0xFF;
+        self.count += 1;
+
+        // Drop the samples older than the window but always keep the newest one
+        while self.count > 1 && now - self.times[tail(self)] > self.params.time as u64 {
+            self.sum -= self.arr[tail(self)] as u32;
+            self.count -= 1;
+        }
+        (self.sum / self.count) as u8
+    }
+
+    /// Feeds the inputs with regular timing and returns the outputs.
+    pub fn run(&mut self, inputs: impl IntoIterator<Item = u8>) -> Vec<u8> {
+        let start = self.time;
+        inputs
+            .into_iter()
+            .enumerate()
+            .map(|(i, input)| self.step(input, start + (i as u64 + 1) * REPORT_NS))
+            .collect()
+    }
+}
+
+//
+// Buttons
+//
+
+/// Remap, toggles and turbo of the normalized buttons. ('mod_device_event')
+pub struct Buttons {
+    remap: [u32; BUTTON_COUNT],
+    periods: [u32; BUTTON_COUNT],
+    toggled: u32,
+    start: [u64; BUTTON_COUNT],
+    latched: u32,
+    previous: u32,
+}
+
+impl Buttons {
+    pub fn new(buttons: &ButtonOptions, turbo: &TurboOptions, toggle: &ToggleOptions) -> Self {
+        Self {
+            remap: buttons.remap_table(),
+            periods: turbo.period_table(),
+            toggled: toggle.mask(),
+            start: [0; BUTTON_COUNT],
+            latched: 0,
+            previous: 0,
+        }
+    }
+
+    /// Returns the output buttons and analog triggers of the input ones.
+    pub fn step(&mut self, input: u32, triggers: [u8; 2], now: u64) -> (u32, [u8; 2]) {
+        let [mut l2, mut r2] = triggers;
+        let mut buttons = self.apply_remap(input);
+
+        // Apply toggles (Latches the analog triggers as well)
+        buttons = self.apply_toggle(buttons);
+        if self.toggled & Button::L2.mask() != 0 {
+            l2 = if buttons & Button::L2.mask() != 0 {
+                0xFF
+            } else {
+                0
+            };
+        }
+        if self.toggled & Button::R2.mask() != 0 {
+            r2 = if buttons & Button::R2.mask() != 0 {
+                0xFF
+            } else {
+                0
+            };
+        }
+
+        // Apply turbo (Releases the analog triggers as well)
+        let held = buttons;
+        buttons = self.apply_turbo(buttons, now);
+        if held & !buttons & Button::L2.mask() != 0 {
+            l2 = 0;
+        }
+        if held & !buttons & Button::R2.mask() != 0 {
+            r2 = 0;
+        }
+
+        (buttons, [l2, r2])
+    }
+
+    fn apply_remap(&self, buttons: u32) -> u32 {
+        (0..BUTTON_COUNT)
+            .filter(|i| buttons & (1 << i) != 0)
+            .fold(0, |output, i| output | self.remap[i])
+    }
+
+    fn apply_toggle(&mut self, buttons: u32) -> u32 {
+        // Flip the latch on every new press
+        self.latched ^= buttons & !self.previous & self.toggled;
+        self.previous = buttons;
+        (buttons & !self.toggled) | (self.latched & self.toggled)
+    }
+
+    fn apply_turbo(&mut self, mut buttons: u32, now: u64) -> u32 {
+        for (i, &period) in self.periods.iter().enumerate() {
+            let mask = 1 << i;
+            if period == 0 || buttons & mask == 0 {
+                self.start[i] = 0;
+                continue;
+            }
+
+            // Pressed for the first half of each period starting from the press
+            if self.start[i] == 0 {
+                self.start[i] = now;
+            }
+            if (now - self.start[i]) % period as u64 >= period as u64 / 2 {
+                buttons &= !mask;
+            }
+        }
+        buttons
+    }
+}
+
+//
+// Motion
+//
+
+/// Transform of a motion sensor. ('apply_motion')
+pub struct Sensor {
+    params: SensorParams,
+    ready: bool,
+    time: u64,
+    value: [i64; 3],
+}
+
+impl Sensor {
+    pub fn new(params: SensorParams) -> Self {
+        Self {
+            params,
+            ready: false,
+            time: 0,
+            value: [0; 3],
+        }
+    }
+
+    pub fn step(&mut self, input: [i16; 3], now: u64) -> [i16; 3] {
+        let dt = delta_time(&mut self.time, now);
+
+        let mut output = [0; 3];
+        for (i, output) in output.iter_mut().enumerate() {
+            let axis = match self.params.axes[i] as usize {
+                axis @ 0..=2 => axis,
+                _ => i,
+            };
+            let mut out = (input[axis] as i64 * self.params.scale[i] as i64) >> 16;
+
+            // Low-pass filter
+            if self.params.cutoff > 0 {
+                let target = out << 16;
+                if !self.ready {
+                    self.value[i] = target;
+                } else {
+                    let alpha = cutoff_alpha(self.params.cutoff as u64, dt) as i64;
+                    self.value[i] += ((target - self.value[i]) * alpha) >> 16;
+                }
+                out = (self.value[i] + 0x8000) >> 16;
+            }
+
+            *output = out.clamp(i16::MIN as i64, i16::MAX as i64) as i16;
+        }
+        self.ready = true;
+        output
+    }
+}
+
+//
+// Gyro
+//
+
+/// Stick offset in raw units of the raw gyro speed. ('gyro_offset')
+pub fn gyro_offset(params: &GyroParams, axis: usize, speed: i16) -> i32 {
+    if (speed as i32).unsigned_abs() < params.deadzone {
+        return 0;
+    }
+    ((speed as i64 * params.scale[axis] as i64) >> 16) as i32
+}
+
+//
+// Touchpad
+//
+
+/// Button of the raw touch position. ('touch_region_buttons', None if outside of the grid)
+pub fn touch_region(params: &TouchRegionParams, x: u32, y: u32) -> Option<u32> {
+    let [columns, rows] = params.grid;
+    if columns == 0 || rows == 0 {
+        return None;
+    }
+    if x < params.min[0] || x > params.max[0] || y < params.min[1] || y > params.max[1] {
+        return None;
+    }
+    let column = (x - params.min[0]) * columns / (params.max[0] - params.min[0] + 1);
+    let row = (y - params.min[1]) * rows / (params.max[1] - params.min[1] + 1);
+    Some(params.buttons[(row * columns + column) as usize])
+}
@@ -0,0 +1,398 @@
+use super::util::fields_to_ne_bytes;
+use anyhow::{Result, bail};
+use serde::Deserialize;
+use std::f64::consts::TAU;
+
+//
+// Options
+//
+
+#[derive(Debug, Default, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum SmoothingMode {
+    /// Moving average over the last reports.
+    #[default]
+    Average,
+    /// Exponential moving average.
+    Ema,
+    /// One-Euro filter. (Adapts to the speed of the input)
+    OneEuro,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone, Copy)]
+#[serde(default)]
+pub struct OneEuroOptions {
+    /// Cutoff frequency in Hz while the input is at rest.
+    pub min_cutoff: f64,
+    /// Increase of the cutoff frequency with speed. (In full ranges per second)
+    pub beta: f64,
+    /// Cutoff frequency in Hz of the speed.
+    pub d_cutoff: f64,
+}
+
+impl Default for OneEuroOptions {
+    fn default() -> Self {
+        Self {
+            min_cutoff: 1.0,
+            beta: 1.0,
+            d_cutoff: 1.0,
+        }
+    }
+}
+
+/// Largest cutoff frequency in Hz and beta of the One-Euro filter.
+const MAX_ONE_EURO: f64 = 1000.0;
+
+impl OneEuroOptions {
+    /// Checks that the cutoffs and beta are within the supported range.
+    pub fn validate(&self) -> Result<()> {
+        for (name, cutoff) in [("min_cutoff", self.min_cutoff), ("d_cutoff", self.d_cutoff)] {
+            if !(cutoff > 0.0 && cutoff <= MAX_ONE_EURO) {
+                bail!(
+                    "One-Euro {name} must be more than 0 and at most {MAX_ONE_EURO} Hz! (got {cutoff})"
+                );
+            }
+        }
+        if !(0.0..=MAX_ONE_EURO).contains(&self.beta) {
+            bail!(
+                "One-Euro beta must be between 0 and {MAX_ONE_EURO}! (got {})",
+                self.beta
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Largest smoothing time in milliseconds.
+const MAX_TIME: f64 = 1000.0;
+
+/// Checks the smoothing time in milliseconds of the mode.
+pub fn validate_time(mode: SmoothingMode, time: Option<f64>) -> Result<()> {
+    let Some(ms) = time else {
+        return Ok(());
+    };
+    if mode == SmoothingMode::OneEuro {
+        bail!("Smoothing time can't be used with the One-Euro filter! (use 'one_euro' instead)");
+    }
+    if !(ms > 0.0 && ms <= MAX_TIME) {
+        bail!("Smoothing time must be more than 0 and at most {MAX_TIME} ms! (got {ms})");
+    }
+    Ok(())
+}
+
+//
+// Parameters
+//
+
+// Smoothing modes (Must match the ones in 'dualsense.bpf.c')
+pub(super) const MODE_AVERAGE: u32 = 0;
+pub(super) const MODE_EMA: u32 = 1;
+pub(super) const MODE_ONE_EURO: u32 = 2;
+
+/// Smoothing parameters in the fixed-point format used by the eBPF program.
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct SmoothingParams {
+    pub mode: u32,
+    /// Window size of the moving average.
+    pub window: u32,
+    /// Time window in ns of the moving average and EMA. (0 if based on report count)
+    pub time: u32,
+    /// Smoothing factor of the EMA. (Q16)
+    pub alpha: u32,
+    /// Minimum cutoff of the One-Euro filter in rad/s. (Q16)
+    pub min_cutoff: u32,
+    /// Cutoff increase per raw unit/s of speed in rad/s. (Q16)
+    pub beta: u32,
+    /// Cutoff of the speed in rad/s. (Q16)
+    pub d_cutoff: u32,
+}
+
+impl SmoothingParams {
+    pub fn new(
+        mode: SmoothingMode,
+        amount: u8,
+        time: Option<f64>,
+        one_euro: &OneEuroOptions,
+    ) -> Self {
+        // Milliseconds to nanoseconds
+        let time = time.map_or(0, |ms| {
+            (ms.clamp(0.0, MAX_TIME) * 1_000_000.0).round() as u32
+        });
+
+        match mode {
+            SmoothingMode::Average => Self {
+                mode: MODE_AVERAGE,
+                window: amount as u32,
+                time,
+                ..Default::default()
+            },
+            SmoothingMode::Ema => Self {
+                mode: MODE_EMA,
+                time,
+                // Same center of mass as the moving average of the same size
+                alpha: to_q16(2.0 / (amount.max(1) as f64 + 1.0)),
+                ..Default::default()
+            },
+            SmoothingMode::OneEuro => Self {
+                mode: MODE_ONE_EURO,
+                min_cutoff: to_q16(TAU * one_euro.min_cutoff),
+                beta: to_q16(TAU * one_euro.beta / u8::MAX as f64),
+                d_cutoff: to_q16(TAU * one_euro.d_cutoff),
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn to_ne_bytes(self) -> [u8; 28] {
+        fields_to_ne_bytes(&[
+            self.mode,
+            self.window,
+            self.time,
+            self.alpha,
+            self.min_cutoff,
+            self.beta,
+            self.d_cutoff,
+        ])
+    }
+}
+
+/// Converts the value into unsigned Q16 fixed-point.
+fn to_q16(value: f64) -> u32 {
+    (value * 65536.0).round() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::reference::{REPORT_NS, Smoothing};
+
+    fn one_euro(min_cutoff: f64, beta: f64, d_cutoff: f64) -> OneEuroOptions {
+        OneEuroOptions {
+            min_cutoff,
+            beta,
+            d_cutoff,
+        }
+    }
+
+    #[test]
+    fn check_params_bytes() {
+        let params = SmoothingParams {
+            mode: 1,
+            window: 2,
+            time: 3,
+            alpha: 4,
+            min_cutoff: 5,
+            beta: 6,
+            d_cutoff: 7,
+        };
+        let bytes = params.to_ne_bytes();
+        assert_eq!(1u32.to_ne_bytes(), bytes[0..4]);
+        assert_eq!(3u32.to_ne_bytes(), bytes[8..12]);
+        assert_eq!(7u32.to_ne_bytes(), bytes[24..28]);
+    }
+
+    #[test]
+    fn check_time() {
+        let params = SmoothingParams::new(SmoothingMode::Ema, 3, Some(8.0), &Default::default());
+        assert_eq!(8_000_000, params.time);
+        let params = SmoothingParams::new(SmoothingMode::Average, 3, None, &Default::default());
+        assert_eq!(0, params.time);
+
+        assert!(validate_time(SmoothingMode::Average, None).is_ok());
+        assert!(validate_time(SmoothingMode::Ema, Some(1000.0)).is_ok());
+        assert!(validate_time(SmoothingMode::Ema, Some(0.0)).is_err());
+        assert!(validate_time(SmoothingMode::Average, Some(-5.0)).is_err());
+        assert!(validate_time(SmoothingMode::Average, Some(1000.5)).is_err());
+        assert!(validate_time(SmoothingMode::Average, Some(f64::NAN)).is_err());
+        // The One-Euro filter is configured by its cutoffs
+        assert!(validate_time(SmoothingMode::OneEuro, None).is_ok());
+        assert!(validate_time(SmoothingMode::OneEuro, Some(8.0)).is_err());
+    }
+
+    #[test]
+    fn check_average() {
+        let params = SmoothingParams::new(SmoothingMode::Average, 3, None, &Default::default());
+        assert_eq!(MODE_AVERAGE, params.mode);
+        assert_eq!(3, params.window);
+        // Only the window is used
+        assert_eq!(
+            [0, 0, 0, 0],
+            [
+                params.alpha,
+                params.min_cutoff,
+                params.beta,
+                params.d_cutoff
+            ]
+        );
+    }
+
+    #[test]
+    fn check_ema() {
+        let alpha = |amount| {
+            SmoothingParams::new(SmoothingMode::Ema, amount, None, &Default::default()).alpha
+        };
+        assert_eq!(
+            MODE_EMA,
+            SmoothingParams::new(SmoothingMode::Ema, 3, None, &Default::default()).mode
+        );
+
+        // Same center of mass as the moving average
+        assert_eq!(32768, alpha(3));
+        assert_eq!(13107, alpha(9));
+        // Amount of 0 or 1 passes the input through
+        assert_eq!(65536, alpha(0));
+        assert_eq!(65536, alpha(1));
+        // Stronger smoothing for larger amounts but never stops following the input
+        assert!((1..=u8::MAX).all(|amount| alpha(amount) >= alpha(amount.saturating_add(1))));
+        assert!(alpha(u8::MAX) > 0);
+    }
+
+    #[test]
+    fn check_one_euro() {
+        let params =
+            SmoothingParams::new(SmoothingMode::OneEuro, 8, None, &one_euro(1.0, 2.0, 0.5));
+
+        assert_eq!(MODE_ONE_EURO, params.mode);
+        assert_eq!(0, params.window);
+        assert_eq!(to_q16(TAU), params.min_cutoff);
+        assert_eq!(to_q16(TAU * 0.5), params.d_cutoff);
+        // Beta is per raw unit instead of full range
+        assert_eq!(to_q16(TAU * 2.0 / 255.0), params.beta);
+
+        // Largest values fit in the fixed-point fields
+        let options = one_euro(MAX_ONE_EURO, MAX_ONE_EURO, MAX_ONE_EURO);
+        let params = SmoothingParams::new(SmoothingMode::OneEuro, 0, None, &options);
+        assert!(params.min_cutoff < u32::MAX / 2, "{params:?}");
+    }
+
+    #[test]
+    fn check_one_euro_rejected() {
+        assert!(OneEuroOptions::default().validate().is_ok());
+        assert!(one_euro(0.01, 0.0, 1000.0).validate().is_ok());
+
+        assert!(one_euro(0.0, 1.0, 1.0).validate().is_err());
+        assert!(one_euro(-1.0, 1.0, 1.0).validate().is_err());
+        assert!(one_euro(1.0, -0.5, 1.0).validate().is_err());
+        assert!(one_euro(1.0, 1.0, 0.0).validate().is_err());
+        assert!(one_euro(1.0, 1.0, 1001.0).validate().is_err());
+        assert!(one_euro(f64::NAN, 1.0, 1.0).validate().is_err());
+    }
+
+    fn one_euro_params(min_cutoff: f64, beta: f64) -> SmoothingParams {
+        SmoothingParams::new(
+            SmoothingMode::OneEuro,
+            0,
+            None,
+            &one_euro(min_cutoff, beta, 1.0),
+        )
+    }
+
+    /// Number of reports needed to get within 1 raw unit of 255 from 0.
+    fn settle_time(params: SmoothingParams) -> usize {
+        let mut filter = Smoothing::new(params);
+        filter.run([0; 8]);
+        filter
+            .run([255; 500])
+            .iter()
+            .position(|&v| v >= 254)
+            .unwrap()
+    }
+
+    #[test]
+    fn check_average_output() {
+        let params = SmoothingParams::new(SmoothingMode::Average, 3, None, &Default::default());
+        let output = Smoothing::new(params).run([100, 200, 0, 100, 100, 100, 100]);
+        assert_eq!(vec![100, 150, 100, 100, 100, 75, 100], output);
+
+        // Window of 1 passes the input through
+        let params = SmoothingParams::new(SmoothingMode::Average, 1, None, &Default::default());
+        let output = Smoothing::new(params).run([100, 200, 0]);
+        assert_eq!(vec![100, 200, 0], output);
+    }
+
+    #[test]
+    fn check_ema_output() {
+        // Step response halves the distance every report
+        let params = SmoothingParams::new(SmoothingMode::Ema, 3, None, &Default::default());
+        let output = Smoothing::new(params).run([0, 255, 255, 255, 255]);
+        assert_eq!(vec![0, 128, 191, 223, 239], output);
+
+        // Converges to constant input
+        let output = Smoothing::new(params).run([200].into_iter().chain([37; 64]));
+        assert_eq!(37, *output.last().unwrap());
+
+        // Amount of 0 or 1 disables smoothing
+        let params = SmoothingParams::new(SmoothingMode::Ema, 1, None, &Default::default());
+        let output = Smoothing::new(params).run([10, 250, 0]);
+        assert_eq!(vec![10, 250, 0], output);
+    }
+
+    #[test]
+    fn check_timed_average() {
+        let params =
+            SmoothingParams::new(SmoothingMode::Average, 0, Some(10.0), &Default::default());
+
+        // Window of 10ms holds 3 reports at 4ms interval
+        let output = Smoothing::new(params).run([30, 60, 90, 120, 150]);
+        assert_eq!(vec![30, 45, 60, 90, 120], output);
+
+        // and 11 reports at 1ms interval
+        let mut filter = Smoothing::new(params);
+        let output: Vec<u8> = (1..=12)
+            .map(|i| filter.step(i * 10, i as u64 * 1_000_000))
+            .collect();
+        assert_eq!(60, output[10]);
+        assert_eq!(70, output[11]);
+    }
+
+    #[test]
+    fn check_timed_ema() {
+        /// Output after 16ms of full input with the report interval.
+        fn output(params: SmoothingParams, interval: u64) -> u8 {
+            let mut filter = Smoothing::new(params);
+            filter.step(0, 1_000_000);
+            let steps = 16_000_000 / interval;
+            (1..=steps)
+                .map(|i| filter.step(255, 1_000_000 + i * interval))
+                .last()
+                .unwrap()
+        }
+
+        // Similar result regardless of the report rate
+        let params = SmoothingParams::new(SmoothingMode::Ema, 0, Some(16.0), &Default::default());
+        let (slow, fast) = (output(params, REPORT_NS), output(params, 1_000_000));
+        assert!(slow.abs_diff(fast) <= 16, "slow = {slow}, fast = {fast}");
+        assert!(slow < 255 && fast < 255, "slow = {slow}, fast = {fast}");
+
+        // Unlike the report based one
+        let params = SmoothingParams::new(SmoothingMode::Ema, 8, None, &Default::default());
+        let (slow, fast) = (output(params, REPORT_NS), output(params, 1_000_000));
+        assert!(slow.abs_diff(fast) > 64, "slow = {slow}, fast = {fast}");
+    }
+
+    #[test]
+    fn check_one_euro_jitter() {
+        let mut filter = Smoothing::new(one_euro_params(1.0, 1.0));
+        filter.run([127; 8]);
+
+        // Jitter at rest gets filtered out
+        let output = filter.run([127, 129, 126, 128, 125, 129, 127, 128].repeat(8));
+        assert!(output.iter().all(|v| (126..=128).contains(v)), "{output:?}");
+    }
+
+    #[test]
+    fn check_one_euro_speed() {
+        // Higher beta reduces the lag of fast movement
+        let slow = settle_time(one_euro_params(1.0, 0.0));
+        let fast = settle_time(one_euro_params(1.0, 5.0));
+        assert!(fast * 4 < slow, "fast = {fast}, slow = {slow}");
+    }
+
+    #[test]
+    fn check_one_euro_converges() {
+        let mut filter = Smoothing::new(one_euro_params(2.0, 1.0));
+        let output = filter.run([0].into_iter().chain([180; 500]));
+        assert_eq!(180, *output.last().unwrap());
+        assert!(output.windows(2).all(|w| w[0] <= w[1]));
+    }
+}
@@ -1,38 +1,285 @@
+use super::buttons::Button;
+use super::curve::Curve;
+use super::smoothing::{OneEuroOptions, SmoothingMode, SmoothingParams, validate_time};
 use super::util::*;
+use anyhow::{Result, bail};
 use glam::DVec2;
 use serde::Deserialize;
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, FRAC_PI_8, SQRT_2};
 
 //
 // Options
 //
 
+#[derive(Debug, Default, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum DeadzoneShape {
+    /// Circular deadzone around the center.
+    #[default]
+    Radial,
+    /// Radial deadzone which always rescales the input to start from the center.
+    ScaledRadial,
+    /// Separate deadzone for each axis.
+    Axial,
+    /// Axial deadzone which widens towards the edge. (Sloped axial)
+    Bowtie,
+    /// Radial deadzone followed by a bowtie deadzone.
+    Hybrid,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum Gate {
+    /// Leave the input space as is.
+    #[default]
+    None,
+    /// Remap the circular input space to a square one.
+    CircleToSquare,
+    /// Remap the square input space to a circular one.
+    SquareToCircle,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum DpadMode {
+    /// D-pad doesn't affect the stick.
+    #[default]
+    Off,
+    /// D-pad replaces the stick position while pressed.
+    Override,
+    /// D-pad is added to the stick position.
+    Add,
+}
+
 #[derive(Debug, Deserialize, PartialEq)]
 #[serde(default)]
 pub struct StickOptions {
     /// Deadzone radius.
     pub deadzone: f64,
+    /// Shape of the deadzone.
+    pub shape: DeadzoneShape,
+    /// Margin past the deadzone the input has to reach to activate.
+    pub hysteresis: f64,
     /// Rescale the input to start from center after deadzone.
     pub rescale: bool,
+    /// Angle in degrees around the cardinal directions to snap to them.
+    pub snap_angle: f64,
+    /// Snap to the diagonal directions as well.
+    pub snap_diagonals: bool,
+    /// Response curve of the radius.
+    pub curve: Curve,
+    /// Radius of the smallest non-zero output.
+    pub anti_deadzone: f64,
+    /// Remapping of the input space.
+    pub gate: Gate,
+    /// Sensitivity multiplier of each axis. (X, Y, Scales the anti-deadzone as well)
+    pub sensitivity: [f64; 2],
     /// Limits the max radius.
     pub limit: Option<f64>,
+    /// Inverts each axis. (X, Y)
+    pub invert: [bool; 2],
+    /// Swaps the X and Y axes.
+    pub swap_axes: bool,
     /// Smoothing over time.
     pub smoothing: u8,
+    /// Smoothing time in milliseconds. (Overrides smoothing)
+    pub smoothing_time: Option<f64>,
+    /// Algorithm used for smoothing.
+    pub smoothing_mode: SmoothingMode,
+    /// Options of the One-Euro filter.
+    pub one_euro: OneEuroOptions,
+    /// Buttons emitted by the stick directions.
+    pub buttons: StickButtons,
+    /// How the D-pad drives the stick.
+    pub dpad: DpadMode,
+    /// Radius of the stick position of the D-pad.
+    pub dpad_magnitude: f64,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct StickButtons {
+    /// Radius at which the buttons get pressed.
+    pub threshold: f64,
+    /// Button of the up direction.
+    pub up: Option<Button>,
+    /// Button of the right direction.
+    pub right: Option<Button>,
+    /// Button of the down direction.
+    pub down: Option<Button>,
+    /// Button of the left direction.
+    pub left: Option<Button>,
+    /// Keep reporting the analog value along with the buttons.
+    pub keep_analog: bool,
+}
+
+impl Default for StickButtons {
+    fn default() -> Self {
+        Self {
+            threshold: 0.5,
+            up: None,
+            right: None,
+            down: None,
+            left: None,
+            keep_analog: false,
+        }
+    }
+}
+
+impl StickButtons {
+    /// Returns true if any of the directions has a button.
+    pub fn enabled(&self) -> bool {
+        [self.up, self.right, self.down, self.left]
+            .iter()
+            .any(Option::is_some)
+    }
+
+    /// Button masks of the up, right, down and left directions followed by the keep analog flag.
+    pub fn to_ne_bytes(&self) -> [u8; 20] {
+        let mask = |button: Option<Button>| button.map_or(0, Button::mask);
+        fields_to_ne_bytes(&[
+            mask(self.up),
+            mask(self.right),
+            mask(self.down),
+            mask(self.left),
+            self.keep_analog as u32,
+        ])
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+#[serde(default)]
+pub struct StickCalibration {
+    /// Offset of the resting position from the ideal center in raw units. (X, Y)
+    pub center: [f64; 2],
+    /// Raw values at the left and up extremes. (X, Y)
+    pub min: [u8; 2],
+    /// Raw values at the right and down extremes. (X, Y)
+    pub max: [u8; 2],
+}
+
+impl Default for StickCalibration {
+    fn default() -> Self {
+        Self {
+            center: [0.0, 0.0],
+            min: [u8::MIN, u8::MIN],
+            max: [u8::MAX, u8::MAX],
+        }
+    }
+}
+
+impl StickCalibration {
+    /// Checks that the resting position is a finite value between the extremes of each axis.
+    pub fn validate(&self) -> Result<()> {
+        for axis in 0..2 {
+            let (center, min, max) = (self.center[axis], self.min[axis], self.max[axis]);
+            let rest = RAW_CENTER + center;
+            if !(center.is_finite() && (min as f64) < rest && rest < max as f64) {
+                bail!(
+                    "Stick calibration must have the center between the min and max! (got {center} with {min} to {max})"
+                );
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Default for StickOptions {
     fn default() -> Self {
         Self {
             deadzone: 0.0,
+            shape: DeadzoneShape::Radial,
+            hysteresis: 0.0,
             rescale: true,
+            snap_angle: 0.0,
+            snap_diagonals: false,
+            curve: Curve::Linear,
+            anti_deadzone: 0.0,
+            gate: Gate::None,
+            sensitivity: [1.0, 1.0],
             limit: None,
+            invert: [false, false],
+            swap_axes: false,
             smoothing: 0,
+            smoothing_time: None,
+            smoothing_mode: SmoothingMode::Average,
+            one_euro: OneEuroOptions::default(),
+            buttons: StickButtons::default(),
+            dpad: DpadMode::Off,
+            dpad_magnitude: 1.0,
         }
     }
 }
 
 impl StickOptions {
-    pub fn gen_lut(&self) -> Vec<u16> {
-        gen_lut(self)
+    /// Checks that the curve and the smoothing options are valid.
+    pub fn validate(&self) -> Result<()> {
+        self.curve.validate()?;
+        self.one_euro.validate()?;
+        validate_time(self.smoothing_mode, self.smoothing_time)
+    }
+
+    pub fn gen_lut(&self, calibration: &StickCalibration) -> Vec<u32> {
+        gen_lut(self, calibration)
+    }
+
+    pub fn smoothing_params(&self) -> SmoothingParams {
+        SmoothingParams::new(
+            self.smoothing_mode,
+            self.smoothing,
+            self.smoothing_time,
+            &self.one_euro,
+        )
+    }
+
+    pub fn dpad_params(&self) -> DpadParams {
+        DpadParams::new(self.dpad, self.dpad_magnitude)
+    }
+}
+
+//
+// D-pad Parameters
+//
+
+// D-pad modes (Must match the ones in 'dualsense.bpf.c')
+const DPAD_OFF: u32 = 0;
+const DPAD_OVERRIDE: u32 = 1;
+const DPAD_ADD: u32 = 2;
+
+/// Stick positions of the D-pad in the format used by the eBPF program.
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct DpadParams {
+    pub mode: u32,
+    /// Raw offsets from the center (X, Y) indexed by the hat switch value. (Up is 0 going clockwise)
+    pub offsets: [[i32; 2]; 8],
+}
+
+impl DpadParams {
+    pub fn new(mode: DpadMode, magnitude: f64) -> Self {
+        let mode = match mode {
+            DpadMode::Off => DPAD_OFF,
+            DpadMode::Override => DPAD_OVERRIDE,
+            DpadMode::Add => DPAD_ADD,
+        };
+
+        let magnitude = magnitude.clamp(0.0, 1.0) * RAW_CENTER;
+        let offsets = std::array::from_fn(|hat| {
+            let angle = hat as f64 * FRAC_PI_4;
+            // Raw values grow towards right and down
+            [angle.sin(), -angle.cos()].map(|v| (v * magnitude).round() as i32)
+        });
+
+        Self { mode, offsets }
+    }
+
+    pub fn to_ne_bytes(self) -> [u8; 68] {
+        let offsets = self
+            .offsets
+            .as_flattened()
+            .iter()
+            .map(|&offset| offset as u32);
+        let fields: Vec<u32> = std::iter::once(self.mode).chain(offsets).collect();
+        fields_to_ne_bytes(&fields)
     }
 }
 
@@ -40,41 +287,211 @@ impl StickOptions {
 // LUT Generator
 //
 
-fn gen_lut(options: &StickOptions) -> Vec<u16> {
+// Flags of the LUT values. (Must match the ones in 'dualsense.bpf.c')
+/// LUT flag for input inside the deadzone.
+const LUT_DEADZONE: u32 = 1 << 16;
+/// LUT flag for input inside the hysteresis margin.
+const LUT_MARGIN: u32 = 1 << 17;
+/// LUT flags of the up, right, down and left directions past the button threshold.
+const LUT_UP: u32 = 1 << 18;
+const LUT_RIGHT: u32 = 1 << 19;
+const LUT_DOWN: u32 = 1 << 20;
+const LUT_LEFT: u32 = 1 << 21;
+
+fn gen_lut(options: &StickOptions, calibration: &StickCalibration) -> Vec<u32> {
     let mut values = vec![0; 256 * 256];
 
     for y in 0..=u8::MAX {
         for x in 0..=u8::MAX {
             let mut input = DVec2::new(to_scaled(x), to_scaled(y));
 
+            apply_calibration(&mut input, calibration);
+            let flags = lut_flags(input, options) | direction_flags(input, options);
+            apply_rest(&mut input, options);
+            let live = live_length(input, options);
             apply_deadzone(&mut input, options);
+            apply_snapping(&mut input, options);
+            apply_curve(&mut input, live, options);
+            apply_gate(&mut input, options);
+            apply_sensitivity(&mut input, options);
             apply_limit(&mut input, options);
+            apply_axes(&mut input, options);
 
             let index = x as usize + y as usize * 256;
-            values[index] = to_merged(input.x, input.y);
+            values[index] = to_merged(input.x, input.y) as u32 | flags;
         }
     }
 
     values
 }
 
+fn apply_calibration(input: &mut DVec2, calibration: &StickCalibration) {
+    let StickCalibration { center, min, max } = calibration;
+    input.x = calibrate_axis(input.x, center[0], min[0], max[0]);
+    input.y = calibrate_axis(input.y, center[1], min[1], max[1]);
+}
+
+fn calibrate_axis(value: f64, center: f64, min: u8, max: u8) -> f64 {
+    // Raw values grow in the opposite direction of the scaled ones
+    let center = -center / RAW_CENTER;
+    let extreme = match value > center {
+        true => to_scaled(min),
+        false => to_scaled(max),
+    };
+    (value - center) / (extreme - center).abs().max(f64::EPSILON)
+}
+
+fn lut_flags(input: DVec2, options: &StickOptions) -> u32 {
+    let inside = |deadzone| {
+        let mut input = input;
+        apply_shape(&mut input, options.shape, deadzone, false);
+        input == DVec2::ZERO
+    };
+
+    if inside(options.deadzone) {
+        LUT_DEADZONE
+    } else if inside(options.deadzone + options.hysteresis) {
+        LUT_MARGIN
+    } else {
+        0
+    }
+}
+
+fn direction_flags(input: DVec2, options: &StickOptions) -> u32 {
+    if !options.buttons.enabled() || input.length() < options.buttons.threshold {
+        return 0;
+    }
+    let Some(dir) = input.try_normalize() else {
+        return 0;
+    };
+
+    // Each direction covers 135 degrees so the diagonals press 2 of them
+    let limit = FRAC_PI_8.sin();
+    // Scaled values grow towards up and left
+    [
+        (dir.y, LUT_UP),
+        (-dir.x, LUT_RIGHT),
+        (-dir.y, LUT_DOWN),
+        (dir.x, LUT_LEFT),
+    ]
+    .iter()
+    .filter(|(value, _)| *value > limit)
+    .fold(0, |flags, (_, flag)| flags | flag)
+}
+
+fn apply_rest(input: &mut DVec2, options: &StickOptions) {
+    // The center is between 2 raw values so the resting position is never exactly zero,
+    // which the anti-deadzone would push out to its radius even without a deadzone
+    if options.anti_deadzone > 0.0 && input.length() <= RAW_STEP {
+        *input = DVec2::ZERO;
+    }
+}
+
 fn apply_deadzone(input: &mut DVec2, options: &StickOptions) {
+    apply_shape(input, options.shape, options.deadzone, options.rescale);
+}
+
+/// Length of the input with the deadzone removed even if the input doesn't get rescaled.
+fn live_length(input: DVec2, options: &StickOptions) -> f64 {
+    let mut input = input;
+    apply_shape(&mut input, options.shape, options.deadzone, true);
+    input.length()
+}
+
+fn apply_shape(input: &mut DVec2, shape: DeadzoneShape, deadzone: f64, rescale: bool) {
+    match shape {
+        DeadzoneShape::Radial => apply_radial(input, deadzone, rescale),
+        DeadzoneShape::ScaledRadial => apply_radial(input, deadzone, true),
+        DeadzoneShape::Axial => apply_axial(input, DVec2::splat(deadzone), rescale),
+        DeadzoneShape::Bowtie => apply_bowtie(input, deadzone, rescale),
+        DeadzoneShape::Hybrid => {
+            apply_radial(input, deadzone, rescale);
+            apply_bowtie(input, deadzone, rescale);
+        }
+    }
+}
+
+fn apply_radial(input: &mut DVec2, deadzone: f64, rescale: bool) {
     let len_squared = input.length_squared();
     // Check if input length is less than the deadzone amount
-    if len_squared < sq(options.deadzone) {
+    if len_squared < sq(deadzone) {
         *input = DVec2::ZERO;
-    } else if options.rescale {
+    } else if rescale {
         #[allow(clippy::collapsible_if)]
         if let Some(dir) = input.try_normalize() {
             let mut len = len_squared.sqrt();
             // Scale the length to take into account the deadzone.
             // Use unscaled lenght if it's longer than 1.0
-            len = deadzone_scale(len, options.deadzone).min(len);
+            len = deadzone_scale(len, deadzone).min(len);
             *input = dir * len;
         }
     }
 }
 
+fn apply_axial(input: &mut DVec2, deadzone: DVec2, rescale: bool) {
+    input.x = axial_deadzone(input.x, deadzone.x, rescale);
+    input.y = axial_deadzone(input.y, deadzone.y, rescale);
+}
+
+fn apply_bowtie(input: &mut DVec2, deadzone: f64, rescale: bool) {
+    // The deadzone of each axis grows with the other axis' value
+    let sloped = DVec2::new(input.y.abs(), input.x.abs()) * deadzone;
+    apply_axial(input, sloped, rescale);
+}
+
+fn axial_deadzone(value: f64, deadzone: f64, rescale: bool) -> f64 {
+    let abs = value.abs();
+    if abs < deadzone {
+        0.0
+    } else if rescale {
+        deadzone_scale(abs, deadzone).min(abs).copysign(value)
+    } else {
+        value
+    }
+}
+
+fn apply_snapping(input: &mut DVec2, options: &StickOptions) {
+    if options.snap_angle <= 0.0 || *input == DVec2::ZERO {
+        return;
+    }
+
+    let step = match options.snap_diagonals {
+        true => FRAC_PI_4,
+        false => FRAC_PI_2,
+    };
+
+    // Snap the direction to the closest one if it's within the angle
+    let angle = input.to_angle();
+    let snapped = (angle / step).round() * step;
+    if (angle - snapped).abs() < options.snap_angle.to_radians() {
+        *input = DVec2::from_angle(snapped) * input.length();
+    }
+}
+
+fn apply_curve(input: &mut DVec2, live: f64, options: &StickOptions) {
+    if let Some(dir) = input.try_normalize() {
+        let len = options.curve.apply(input.length());
+        let len = match options.rescale {
+            true => anti_deadzone_scale(len, options.anti_deadzone),
+            // Start the anti-deadzone from the deadzone edge even without rescaling
+            false => anti_deadzone_floor(len, options.curve.apply(live), options.anti_deadzone),
+        };
+        *input = dir * len;
+    }
+}
+
+fn apply_gate(input: &mut DVec2, options: &StickOptions) {
+    match options.gate {
+        Gate::None => (),
+        Gate::CircleToSquare => *input = circle_to_square(input.clamp_length_max(1.0)),
+        Gate::SquareToCircle => *input = square_to_circle(input.clamp(DVec2::NEG_ONE, DVec2::ONE)),
+    }
+}
+
+fn apply_sensitivity(input: &mut DVec2, options: &StickOptions) {
+    *input *= DVec2::from(options.sensitivity);
+}
+
 fn apply_limit(input: &mut DVec2, options: &StickOptions) {
     if let Some(limit) = options.limit {
         #[allow(clippy::collapsible_if)]
@@ -85,11 +502,44 @@ fn apply_limit(input: &mut DVec2, options: &StickOptions) {
     }
 }
 
+fn apply_axes(input: &mut DVec2, options: &StickOptions) {
+    if options.swap_axes {
+        *input = DVec2::new(input.y, input.x);
+    }
+    if options.invert[0] {
+        input.x = -input.x;
+    }
+    if options.invert[1] {
+        input.y = -input.y;
+    }
+}
+
 //
 // Utility
 //
 
+/// Elliptical grid mapping from the unit disc to the unit square.
+fn circle_to_square(disc: DVec2) -> DVec2 {
+    fn axis(a: f64, b: f64) -> f64 {
+        let common = 2.0 + sq(a) - sq(b);
+        let offset = 2.0 * SQRT_2 * a;
+        0.5 * ((common + offset).max(0.0).sqrt() - (common - offset).max(0.0).sqrt())
+    }
+
+    DVec2::new(axis(disc.x, disc.y), axis(disc.y, disc.x)).clamp(DVec2::NEG_ONE, DVec2::ONE)
+}
+
+/// Elliptical grid mapping from the unit square to the unit disc.
+fn square_to_circle(square: DVec2) -> DVec2 {
+    DVec2::new(
+        square.x * (1.0 - sq(square.y) / 2.0).sqrt(),
+        square.y * (1.0 - sq(square.x) / 2.0).sqrt(),
+    )
+}
+
 const RAW_CENTER: f64 = u8::MAX as f64 / 2.0;
+/// Length of a single raw step in scaled units.
+const RAW_STEP: f64 = 1.0 / RAW_CENTER;
 
 /// Converts raw value into -1.0 to 1.0 range.
 fn to_scaled(raw: u8) -> f64 {
@@ -144,4 +594,394 @@ mod tests {
         assert_eq!(x, (merged & 0xFF) as u8);
         assert_eq!(y, ((merged >> 8) & 0xFF) as u8);
     }
+
+    fn deadzone(shape: DeadzoneShape, x: f64, y: f64) -> DVec2 {
+        let options = StickOptions {
+            deadzone: 0.2,
+            shape,
+            ..Default::default()
+        };
+        let mut input = DVec2::new(x, y);
+        apply_deadzone(&mut input, &options);
+        input
+    }
+
+    fn assert_near(expected: (f64, f64), actual: DVec2) {
+        let diff = (DVec2::new(expected.0, expected.1) - actual).abs();
+        assert!(
+            diff.max_element() < 1e-9,
+            "expected = {expected:?}, actual = {actual}"
+        );
+    }
+
+    #[test]
+    fn check_radial() {
+        assert_near((0.0, 0.0), deadzone(DeadzoneShape::Radial, 0.1, 0.1));
+        assert_near((0.0, 0.0), deadzone(DeadzoneShape::Radial, 0.0, -0.19));
+        assert_near((0.0, 0.375), deadzone(DeadzoneShape::Radial, 0.0, 0.5));
+        assert_near((-1.0, 0.0), deadzone(DeadzoneShape::Radial, -1.0, 0.0));
+        // Cross-axis value leaks into the output
+        assert!(deadzone(DeadzoneShape::Radial, 0.1, 1.0).x > 0.0);
+    }
+
+    #[test]
+    fn check_axial() {
+        assert_near((0.0, 0.0), deadzone(DeadzoneShape::Axial, 0.1, 0.1));
+        assert_near((0.0, 1.0), deadzone(DeadzoneShape::Axial, 0.1, 1.0));
+        assert_near((-0.375, 0.0), deadzone(DeadzoneShape::Axial, -0.5, 0.19));
+        assert_near((1.0, -1.0), deadzone(DeadzoneShape::Axial, 1.0, -1.0));
+    }
+
+    #[test]
+    fn check_bowtie() {
+        // Deadzone shrinks towards the center
+        assert_near((0.05, 0.0), deadzone(DeadzoneShape::Bowtie, 0.05, 0.0));
+        // and grows towards the edge
+        assert_near((0.0, 1.0), deadzone(DeadzoneShape::Bowtie, 0.19, 1.0));
+        assert_near((-1.0, 0.0), deadzone(DeadzoneShape::Bowtie, -1.0, 0.19));
+        assert_near((1.0, 1.0), deadzone(DeadzoneShape::Bowtie, 1.0, 1.0));
+    }
+
+    #[test]
+    fn check_hybrid() {
+        assert_near((0.0, 0.0), deadzone(DeadzoneShape::Hybrid, 0.1, 0.1));
+        assert_near((0.0, 0.0), deadzone(DeadzoneShape::Hybrid, 0.05, 0.0));
+        assert_near((0.0, 1.0), deadzone(DeadzoneShape::Hybrid, 0.15, 1.0));
+        assert_near((-1.0, 0.0), deadzone(DeadzoneShape::Hybrid, -1.0, 0.0));
+        let diagonal = deadzone(DeadzoneShape::Hybrid, 0.7, 0.7);
+        assert!(diagonal.x > 0.0 && (diagonal.x - diagonal.y).abs() < 1e-9);
+    }
+
+    #[test]
+    fn check_curve() {
+        let options = StickOptions {
+            curve: Curve::Power(2.0),
+            ..Default::default()
+        };
+        let mut input = DVec2::new(0.3, -0.4);
+        apply_curve(&mut input, 0.5, &options);
+        assert_near((0.15, -0.2), input);
+    }
+
+    #[test]
+    fn check_anti_deadzone() {
+        let options = StickOptions {
+            deadzone: 0.2,
+            anti_deadzone: 0.3,
+            limit: Some(0.9),
+            ..Default::default()
+        };
+        let lut = options.gen_lut(&StickCalibration::default());
+        let output = |x: u8, y: u8| {
+            let merged = lut[x as usize + y as usize * 256];
+            DVec2::new(to_scaled(merged as u8), to_scaled((merged >> 8) as u8))
+        };
+
+        let center = to_merged(0.0, 0.0) as u32;
+        assert_eq!(center, lut[127 + 127 * 256] & 0xFFFF);
+        assert_eq!(center, lut[127 + 103 * 256] & 0xFFFF);
+        // First raw value past the deadzone lands outside the anti-deadzone
+        let first = output(127, 102).length();
+        assert!((0.3..0.31).contains(&first), "first = {first}");
+        // Limit still applies
+        let last = output(127, 0).length();
+        assert!((last - 0.9).abs() < 0.01, "last = {last}");
+    }
+
+    #[test]
+    fn check_anti_deadzone_unscaled() {
+        let options = StickOptions {
+            deadzone: 0.2,
+            rescale: false,
+            anti_deadzone: 0.3,
+            ..Default::default()
+        };
+        let lut = options.gen_lut(&StickCalibration::default());
+        let output = |x: u8, y: u8| {
+            let merged = lut[x as usize + y as usize * 256];
+            DVec2::new(to_scaled(merged as u8), to_scaled((merged >> 8) as u8))
+        };
+
+        // Starts from the anti-deadzone instead of the deadzone edge
+        let first = output(127, 102).length();
+        assert!((0.3..0.31).contains(&first), "first = {first}");
+        assert!((output(127, 0).length() - 1.0).abs() < 0.01);
+
+        // Anti-deadzone smaller than the deadzone keeps the unscaled input
+        let options = StickOptions {
+            anti_deadzone: 0.1,
+            ..options
+        };
+        let lut = options.gen_lut(&StickCalibration::default());
+        let first = to_scaled((lut[127 + 102 * 256] >> 8) as u8).abs();
+        assert!((0.2..0.21).contains(&first), "first = {first}");
+    }
+
+    #[test]
+    fn check_anti_deadzone_center() {
+        let options = StickOptions {
+            anti_deadzone: 0.3,
+            ..Default::default()
+        };
+        let lut = options.gen_lut(&StickCalibration::default());
+        let output = |x: u8, y: u8| {
+            let merged = lut[x as usize + y as usize * 256];
+            DVec2::new(to_scaled(merged as u8), to_scaled((merged >> 8) as u8))
+        };
+
+        // Resting positions around the center stay there without a deadzone
+        let center = to_merged(0.0, 0.0) as u32;
+        for (x, y) in [(127, 127), (128, 128), (127, 128), (128, 127)] {
+            assert_eq!(center, lut[x + y * 256] & 0xFFFF, "({x}, {y})");
+        }
+        // Next raw value jumps to the anti-deadzone
+        let first = output(126, 127).length();
+        assert!((0.3..0.32).contains(&first), "first = {first}");
+    }
+
+    #[test]
+    fn check_axes() {
+        let options = StickOptions {
+            sensitivity: [0.5, 2.0],
+            invert: [true, false],
+            swap_axes: true,
+            ..Default::default()
+        };
+        let lut = options.gen_lut(&StickCalibration::default());
+        let output = |x: u8, y: u8| {
+            let merged = lut[x as usize + y as usize * 256];
+            (merged as u8, (merged >> 8) as u8)
+        };
+
+        // Full X (right) becomes half Y (down)
+        assert_eq!(191, output(255, 127).1);
+        // Half Y (up) becomes full X (right) after inversion
+        assert_eq!(255, output(127, 63).0);
+        // Values out of range get clamped
+        assert_eq!(255, output(127, 0).0);
+    }
+
+    #[test]
+    fn check_snapping() {
+        fn snap(diagonals: bool, x: f64, y: f64) -> DVec2 {
+            let options = StickOptions {
+                snap_angle: 10.0,
+                snap_diagonals: diagonals,
+                ..Default::default()
+            };
+            let mut input = DVec2::new(x, y);
+            apply_snapping(&mut input, &options);
+            input
+        }
+
+        let near_up = DVec2::from_angle(95f64.to_radians()) * 0.5;
+        assert_near((0.0, 0.5), snap(false, near_up.x, near_up.y));
+        let near_left = DVec2::from_angle(-171f64.to_radians());
+        assert_near((-1.0, 0.0), snap(false, near_left.x, near_left.y));
+        let outside = DVec2::from_angle(-101f64.to_radians());
+        assert_near((outside.x, outside.y), snap(false, outside.x, outside.y));
+
+        let near_diagonal = DVec2::from_angle(40f64.to_radians());
+        let diagonal = DVec2::from_angle(45f64.to_radians());
+        assert_near(
+            (diagonal.x, diagonal.y),
+            snap(true, near_diagonal.x, near_diagonal.y),
+        );
+        assert_near(
+            (near_diagonal.x, near_diagonal.y),
+            snap(false, near_diagonal.x, near_diagonal.y),
+        );
+
+        assert_near((0.0, 0.0), snap(true, 0.0, 0.0));
+    }
+
+    #[test]
+    fn check_gate() {
+        fn gate(gate: Gate, x: u8, y: u8) -> (u8, u8) {
+            let lut = StickOptions {
+                gate,
+                ..Default::default()
+            }
+            .gen_lut(&StickCalibration::default());
+            let merged = lut[x as usize + y as usize * 256];
+            (merged as u8, (merged >> 8) as u8)
+        }
+
+        // Corners of the raw range
+        assert_eq!((255, 0), gate(Gate::None, 255, 0));
+        assert_eq!((255, 0), gate(Gate::CircleToSquare, 255, 0));
+        assert_eq!((218, 37), gate(Gate::SquareToCircle, 255, 0));
+
+        // Diagonal of the round gate reaches the corner
+        assert_eq!((0, 255), gate(Gate::CircleToSquare, 37, 218));
+        // Edges are unaffected
+        assert_eq!((255, 127), gate(Gate::CircleToSquare, 255, 127));
+        assert_eq!((127, 0), gate(Gate::SquareToCircle, 127, 0));
+        // Center is unaffected
+        assert_eq!((127, 127), gate(Gate::CircleToSquare, 127, 127));
+        assert_eq!((127, 127), gate(Gate::SquareToCircle, 127, 127));
+    }
+
+    #[test]
+    fn check_gate_roundtrip() {
+        for (x, y) in [(0.5, 0.5), (-0.3, 0.8), (0.7, -0.7), (-0.1, -0.2)] {
+            let square = DVec2::new(x, y);
+            let circle = square_to_circle(square);
+            assert!(circle.length() <= 1.0);
+            assert_near((x, y), circle_to_square(circle));
+        }
+    }
+
+    #[test]
+    fn check_calibration() {
+        // Default calibration leaves the input as is
+        let lut = StickOptions::default().gen_lut(&StickCalibration::default());
+        for (index, &value) in lut.iter().enumerate() {
+            assert_eq!(index as u32, value);
+        }
+
+        let calibration = StickCalibration {
+            center: [3.0, -2.0],
+            min: [10, 0],
+            max: [250, 255],
+        };
+        let lut = StickOptions {
+            deadzone: 0.05,
+            ..Default::default()
+        }
+        .gen_lut(&calibration);
+        let output = |x: u8, y: u8| {
+            let merged = lut[x as usize + y as usize * 256];
+            (merged as u8, (merged >> 8) as u8)
+        };
+
+        // Offset resting position is within the deadzone
+        assert_eq!((127, 127), output(130, 125));
+        // Extremes reach the full range
+        assert_eq!(0, output(10, 125).0);
+        assert_eq!(255, output(250, 125).0);
+        assert_eq!(0, output(130, 0).1);
+        assert_eq!(255, output(130, 255).1);
+    }
+
+    #[test]
+    fn check_hysteresis() {
+        let lut = StickOptions {
+            deadzone: 0.2,
+            hysteresis: 0.1,
+            ..Default::default()
+        }
+        .gen_lut(&StickCalibration::default());
+        let flags = |x: u8, y: u8| lut[x as usize + y as usize * 256] & (LUT_DEADZONE | LUT_MARGIN);
+
+        assert_eq!(LUT_DEADZONE, flags(127, 127));
+        assert_eq!(LUT_DEADZONE, flags(127, 103));
+        assert_eq!(LUT_MARGIN, flags(127, 102));
+        assert_eq!(LUT_MARGIN, flags(135, 95));
+        assert_eq!(0, flags(127, 89));
+        assert_eq!(0, flags(0, 0));
+
+        // Output inside the margin is unaffected
+        assert_ne!(to_merged(0.0, 0.0) as u32, lut[127 + 95 * 256] & 0xFFFF);
+    }
+
+    #[test]
+    fn check_scaled_radial() {
+        let options: StickOptions = toml::from_str("shape = 'scaled-radial'").unwrap();
+        assert_eq!(DeadzoneShape::ScaledRadial, options.shape);
+
+        // Rescales even with rescale turned off
+        let unscaled = |shape| {
+            let options = StickOptions {
+                deadzone: 0.2,
+                shape,
+                rescale: false,
+                ..Default::default()
+            };
+            let mut input = DVec2::new(0.0, 0.5);
+            apply_deadzone(&mut input, &options);
+            input
+        };
+        assert_near((0.0, 0.375), unscaled(DeadzoneShape::ScaledRadial));
+        assert_near((0.0, 0.5), unscaled(DeadzoneShape::Radial));
+        assert_near(
+            (0.0, 0.375),
+            deadzone(DeadzoneShape::ScaledRadial, 0.0, 0.5),
+        );
+        assert_near((0.0, 0.0), deadzone(DeadzoneShape::ScaledRadial, 0.1, 0.1));
+    }
+
+    #[test]
+    fn check_directions() {
+        let options: StickOptions =
+            toml::from_str("buttons = { up = 'dpad-up', threshold = 0.5 }").unwrap();
+        assert_eq!(Some(Button::DpadUp), options.buttons.up);
+
+        let lut = options.gen_lut(&StickCalibration::default());
+        let flags = |x: u8, y: u8| {
+            lut[x as usize + y as usize * 256] & (LUT_UP | LUT_RIGHT | LUT_DOWN | LUT_LEFT)
+        };
+
+        assert_eq!(0, flags(127, 127));
+        assert_eq!(0, flags(127, 70));
+        assert_eq!(LUT_UP, flags(127, 60));
+        assert_eq!(LUT_DOWN, flags(127, 255));
+        assert_eq!(LUT_LEFT, flags(0, 127));
+        assert_eq!(LUT_RIGHT, flags(255, 127));
+        assert_eq!(LUT_UP | LUT_RIGHT, flags(255, 0));
+        assert_eq!(LUT_DOWN | LUT_LEFT, flags(0, 255));
+        // Close to the cardinal direction presses only that one
+        assert_eq!(LUT_UP, flags(160, 0));
+    }
+
+    #[test]
+    fn check_stick_buttons_bytes() {
+        let buttons = StickButtons {
+            up: Some(Button::DpadUp),
+            left: Some(Button::Square),
+            ..Default::default()
+        };
+        let bytes = buttons.to_ne_bytes();
+        let value = |i: usize| u32::from_ne_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap());
+
+        assert_eq!(Button::DpadUp.mask(), value(0));
+        assert_eq!(0, value(1));
+        assert_eq!(0, value(2));
+        assert_eq!(Button::Square.mask(), value(3));
+        assert_eq!(0, value(4));
+    }
+
+    #[test]
+    fn check_dpad_params() {
+        let params = DpadParams::new(DpadMode::Override, 1.0);
+        assert_eq!(DPAD_OVERRIDE, params.mode);
+        assert_eq!([0, -128], params.offsets[0]); // Up
+        assert_eq!([90, -90], params.offsets[1]); // Up-Right
+        assert_eq!([128, 0], params.offsets[2]); // Right
+        assert_eq!([0, 128], params.offsets[4]); // Down
+        assert_eq!([-128, 0], params.offsets[6]); // Left
+
+        let params = DpadParams::new(DpadMode::Add, 0.5);
+        assert_eq!(DPAD_ADD, params.mode);
+        assert_eq!([64, 0], params.offsets[2]);
+
+        let bytes = params.to_ne_bytes();
+        let value = |i: usize| i32::from_ne_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap());
+        assert_eq!(DPAD_ADD as i32, value(0));
+        assert_eq!(64, value(5)); // Right X
+        assert_eq!(-64, value(13)); // Left X
+    }
+
+    #[test]
+    fn check_calibration_rejected() {
+        let calibration = |center, min, max| StickCalibration { center, min, max }.validate();
+        assert!(StickCalibration::default().validate().is_ok());
+        assert!(calibration([-3.5, 4.0], [10, 20], [240, 250]).is_ok());
+        assert!(calibration([f64::NAN, 0.0], [0, 0], [255, 255]).is_err());
+        assert!(calibration([0.0, f64::INFINITY], [0, 0], [255, 255]).is_err());
+        // Center outside of the extremes
+        assert!(calibration([-120.0, 0.0], [10, 0], [255, 255]).is_err());
+        assert!(calibration([0.0, 0.0], [0, 130], [255, 255]).is_err());
+        assert!(calibration([0.0, 0.0], [0, 0], [255, 127]).is_err());
+    }
 }
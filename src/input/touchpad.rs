@@ -0,0 +1,347 @@
+use super::buttons::Button;
+use super::util::fields_to_ne_bytes;
+use anyhow::{Result, bail};
+use serde::Deserialize;
+
+//
+// Options
+//
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct TouchpadOptions {
+    /// Report the touches.
+    pub enabled: bool,
+    /// Width of the ignored edges. (Left, Top, Right, Bottom)
+    pub edges: [f64; 4],
+    /// Movement smaller than this percentage of the width is ignored.
+    pub deadzone: f64,
+    /// Regions of the touchpad used as buttons.
+    pub regions: TouchRegions,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum RegionActivation {
+    /// Touching the region presses the button.
+    #[default]
+    Touch,
+    /// Clicking the region presses the button instead of the touchpad click.
+    Click,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct TouchRegions {
+    /// Number of columns and rows of the grid.
+    pub grid: [u32; 2],
+    /// Buttons of the regions in row-major order.
+    pub buttons: Vec<Button>,
+    /// What presses the button of the region.
+    pub activation: RegionActivation,
+}
+
+impl Default for TouchRegions {
+    fn default() -> Self {
+        Self {
+            grid: [2, 1],
+            buttons: Vec::new(),
+            activation: RegionActivation::Touch,
+        }
+    }
+}
+
+/// Largest number of columns and rows. (Fits in the regions of the eBPF program)
+const MAX_GRID: u32 = 4;
+
+impl TouchRegions {
+    /// Checks that the grid size is supported and has a region for every button.
+    pub fn validate(&self) -> Result<()> {
+        let [columns, rows] = self.grid;
+        if !(1..=MAX_GRID).contains(&columns) || !(1..=MAX_GRID).contains(&rows) {
+            bail!(
+                "Touchpad grid must have 1 to {MAX_GRID} columns and rows! (got {columns}x{rows})"
+            );
+        }
+        if self.buttons.len() > (columns * rows) as usize {
+            bail!(
+                "Touchpad grid of {columns}x{rows} has fewer regions than buttons! (got {})",
+                self.buttons.len()
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Default for TouchpadOptions {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            edges: [0.0, 0.0, 0.0, 0.0],
+            deadzone: 0.0,
+            regions: TouchRegions::default(),
+        }
+    }
+}
+
+impl TouchpadOptions {
+    /// Checks the edges, deadzone and regions. (The edges must leave some of the touchpad unmasked)
+    pub fn validate(&self) -> Result<()> {
+        let [left, top, right, bottom] = self.edges;
+        if !self.edges.iter().all(|edge| (0.0..1.0).contains(edge)) {
+            bail!(
+                "Touchpad edges must be at least 0 and less than 1! (got {:?})",
+                self.edges
+            );
+        }
+        // Masking the whole touchpad would silently drop every touch
+        if left + right >= 1.0 || top + bottom >= 1.0 {
+            bail!(
+                "Touchpad edges must leave some of the touchpad unmasked! (got {:?})",
+                self.edges
+            );
+        }
+        if !(0.0..=1.0).contains(&self.deadzone) {
+            bail!(
+                "Touchpad deadzone must be between 0 and 1! (got {})",
+                self.deadzone
+            );
+        }
+        self.regions.validate()
+    }
+
+    pub fn params(&self) -> TouchpadParams {
+        TouchpadParams::new(self)
+    }
+
+    pub fn region_params(&self) -> TouchRegionParams {
+        TouchRegionParams::new(self)
+    }
+}
+
+//
+// Parameters
+//
+
+// Resolution of the touchpad (Must match the one in 'dualsense.h')
+const TOUCHPAD_WIDTH: u32 = 1920;
+const TOUCHPAD_HEIGHT: u32 = 1080;
+
+/// Touchpad parameters in the raw units used by the eBPF program.
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct TouchpadParams {
+    pub enabled: u32,
+    /// Smallest reported coordinates. (X, Y)
+    pub min: [u32; 2],
+    /// Largest reported coordinates. (X, Y)
+    pub max: [u32; 2],
+    /// Movement smaller than this is ignored.
+    pub deadzone: u32,
+}
+
+impl TouchpadParams {
+    pub fn new(options: &TouchpadOptions) -> Self {
+        let to_raw =
+            |value: f64, size: u32| (value.clamp(0.0, 1.0) * (size - 1) as f64).round() as u32;
+        let [left, top, right, bottom] = options.edges;
+
+        Self {
+            enabled: options.enabled as u32,
+            min: [to_raw(left, TOUCHPAD_WIDTH), to_raw(top, TOUCHPAD_HEIGHT)],
+            max: [
+                to_raw(1.0 - right, TOUCHPAD_WIDTH),
+                to_raw(1.0 - bottom, TOUCHPAD_HEIGHT),
+            ],
+            deadzone: to_raw(options.deadzone, TOUCHPAD_WIDTH),
+        }
+    }
+
+    pub fn to_ne_bytes(self) -> [u8; 24] {
+        fields_to_ne_bytes(&[
+            self.enabled,
+            self.min[0],
+            self.min[1],
+            self.max[0],
+            self.max[1],
+            self.deadzone,
+        ])
+    }
+}
+
+/// Largest number of regions. (Must match the one in 'dualsense.bpf.c')
+const MAX_REGIONS: usize = 16;
+
+/// Touchpad region parameters in the format used by the eBPF program.
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct TouchRegionParams {
+    /// Number of columns and rows of the grid. (0 if disabled)
+    pub grid: [u32; 2],
+    /// Regions get pressed by clicking instead of touching.
+    pub click: u32,
+    /// Smallest coordinates of the grid. (X, Y)
+    pub min: [u32; 2],
+    /// Largest coordinates of the grid. (X, Y)
+    pub max: [u32; 2],
+    /// Button masks of the regions in row-major order.
+    pub buttons: [u32; MAX_REGIONS],
+}
+
+impl TouchRegionParams {
+    pub fn new(options: &TouchpadOptions) -> Self {
+        // The regions only see the touches that are reported
+        let regions = &options.regions;
+        if !options.enabled || regions.buttons.is_empty() {
+            return Self::default();
+        }
+
+        let mut buttons = [0; MAX_REGIONS];
+        for (mask, button) in buttons.iter_mut().zip(&regions.buttons) {
+            *mask = button.mask();
+        }
+
+        // The grid covers the area inside the masked edges
+        let TouchpadParams { min, max, .. } = options.params();
+        Self {
+            grid: regions.grid,
+            click: (regions.activation == RegionActivation::Click) as u32,
+            min,
+            max,
+            buttons,
+        }
+    }
+
+    pub fn to_ne_bytes(self) -> [u8; 92] {
+        let fields = [
+            self.grid[0],
+            self.grid[1],
+            self.click,
+            self.min[0],
+            self.min[1],
+            self.max[0],
+            self.max[1],
+        ];
+        fields_to_ne_bytes(&[fields.as_slice(), &self.buttons].concat())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::reference::touch_region as region;
+
+    #[test]
+    fn check_default() {
+        let params = TouchpadOptions::default().params();
+
+        assert_eq!(1, params.enabled);
+        assert_eq!([0, 0], params.min);
+        assert_eq!([1919, 1079], params.max);
+        assert_eq!(0, params.deadzone);
+    }
+
+    #[test]
+    fn check_edges() {
+        let options: TouchpadOptions =
+            toml::from_str("edges = [0.1, 0.0, 0.25, 0.5]\ndeadzone = 0.01").unwrap();
+        let params = options.params();
+
+        assert_eq!([192, 0], params.min);
+        assert_eq!([1439, 540], params.max);
+        assert_eq!(19, params.deadzone);
+    }
+
+    #[test]
+    fn check_disabled() {
+        let options: TouchpadOptions = toml::from_str("enabled = false").unwrap();
+        assert_eq!(0, options.params().enabled);
+    }
+
+    #[test]
+    fn check_rejected() {
+        let parse = |toml: &str| toml::from_str::<TouchpadOptions>(toml).unwrap().validate();
+        assert!(parse("edges = [0.4, 0.0, 0.5, 0.99]\ndeadzone = 1.0").is_ok());
+        assert!(parse("edges = [0.5, 0.0, 0.5, 0.0]").is_err());
+        assert!(parse("edges = [0.0, 0.7, 0.0, 0.4]").is_err());
+        assert!(parse("edges = [1.0, 0.0, 0.0, 0.0]").is_err());
+        assert!(parse("edges = [-0.1, 0.0, 0.0, 0.0]").is_err());
+        assert!(parse("edges = [0.0, nan, 0.0, 0.0]").is_err());
+        assert!(parse("deadzone = -0.1").is_err());
+    }
+
+    #[test]
+    fn check_regions() {
+        let options: TouchpadOptions = toml::from_str(
+            "[regions]\ngrid = [2, 2]\nbuttons = ['l1', 'r1', 'l3']\nactivation = 'click'",
+        )
+        .unwrap();
+        let params = options.region_params();
+
+        assert_eq!([2, 2], params.grid);
+        assert_eq!(1, params.click);
+        assert_eq!(Some(Button::L1.mask()), region(&params, 0, 0));
+        assert_eq!(Some(Button::L1.mask()), region(&params, 959, 539));
+        assert_eq!(Some(Button::R1.mask()), region(&params, 960, 0));
+        assert_eq!(Some(Button::L3.mask()), region(&params, 0, 1079));
+        // Regions without a button
+        assert_eq!(Some(0), region(&params, 1919, 1079));
+
+        let bytes = params.to_ne_bytes();
+        assert_eq!(1079u32.to_ne_bytes(), bytes[24..28]);
+        assert_eq!(Button::L1.mask().to_ne_bytes(), bytes[28..32]);
+    }
+
+    #[test]
+    fn check_regions_masked() {
+        let options: TouchpadOptions =
+            toml::from_str("edges = [0.25, 0.0, 0.25, 0.5]\nregions = { buttons = ['l1', 'r1'] }")
+                .unwrap();
+        let params = options.region_params();
+
+        // Touches on the masked edges press nothing
+        assert_eq!(None, region(&params, 100, 100));
+        assert_eq!(None, region(&params, 1800, 100));
+        assert_eq!(None, region(&params, 960, 800));
+        // The halves split the area inside the edges
+        assert_eq!(Some(Button::L1.mask()), region(&params, 480, 0));
+        assert_eq!(Some(Button::L1.mask()), region(&params, 958, 0));
+        assert_eq!(Some(Button::R1.mask()), region(&params, 961, 0));
+        assert_eq!(Some(Button::R1.mask()), region(&params, 1439, 539));
+    }
+
+    #[test]
+    fn check_regions_rejected() {
+        let parse = |toml: &str| toml::from_str::<TouchpadOptions>(toml).unwrap().validate();
+        assert!(parse("regions = { grid = [4, 4] }").is_ok());
+        assert!(parse("regions = { grid = [0, 1] }").is_err());
+        assert!(parse("regions = { grid = [2, 5] }").is_err());
+        // More buttons than regions
+        assert!(parse("regions = { grid = [2, 1], buttons = ['l1', 'r1'] }").is_ok());
+        assert!(parse("regions = { grid = [2, 1], buttons = ['l1', 'r1', 'l3'] }").is_err());
+    }
+
+    #[test]
+    fn check_regions_disabled() {
+        let params = TouchpadOptions::default().region_params();
+        assert_eq!(TouchRegionParams::default(), params);
+        assert_eq!(None, region(&params, 0, 0));
+
+        // Left and right halves by default
+        let mut options = TouchpadOptions {
+            regions: TouchRegions {
+                buttons: vec![Button::Square, Button::Circle],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let params = options.region_params();
+        assert_eq!([2, 1], params.grid);
+        assert_eq!(0, params.click);
+        assert_eq!(Some(Button::Circle.mask()), region(&params, 1500, 800));
+
+        // Disabling the touchpad disables the regions as well
+        options.enabled = false;
+        let params = options.region_params();
+        assert_eq!(TouchRegionParams::default(), params);
+        assert_eq!(None, region(&params, 1500, 800));
+    }
+}
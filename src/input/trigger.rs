@@ -1,32 +1,140 @@
+use super::curve::Curve;
+use super::smoothing::{OneEuroOptions, SmoothingMode, SmoothingParams, validate_time};
 use super::util::*;
+use anyhow::{Result, bail};
 use serde::Deserialize;
 
 //
 // Options
 //
 
+#[derive(Debug, Default, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum TriggerMode {
+    /// Reports the analog value.
+    #[default]
+    Analog,
+    /// Reports full press once past the deadzone.
+    Digital,
+    /// Reports full press after moving down and release after moving up by the hair travel.
+    Hair,
+}
+
 #[derive(Debug, Deserialize, PartialEq)]
 #[serde(default)]
 pub struct TriggerOptions {
+    /// Operating mode.
+    pub mode: TriggerMode,
+    /// Travel percentage needed to press or release in hair mode.
+    pub hair_travel: f64,
     /// Deadzone percentage.
     pub deadzone: f64,
+    /// Outer deadzone percentage. (Distance from full press)
+    pub outer_deadzone: f64,
     /// Rescale the value to start from 0.0 after deadzone.
     pub rescale: bool,
+    /// Response curve of the value.
+    pub curve: Curve,
+    /// Smallest non-zero value.
+    pub anti_deadzone: f64,
+    /// Changes smaller than this percentage are ignored.
+    pub jitter: f64,
+    /// Smoothing over time.
+    pub smoothing: u8,
+    /// Smoothing time in milliseconds. (Overrides smoothing)
+    pub smoothing_time: Option<f64>,
+    /// Algorithm used for smoothing.
+    pub smoothing_mode: SmoothingMode,
+    /// Options of the One-Euro filter.
+    pub one_euro: OneEuroOptions,
+    /// Press and release levels of the digital button.
+    pub digital_threshold: DigitalThreshold,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Clone, Copy)]
+#[serde(default)]
+pub struct DigitalThreshold {
+    /// Value at which the button gets pressed.
+    pub press: f64,
+    /// Value below which the button gets released. (Same as press if unset)
+    pub release: Option<f64>,
+}
+
+impl DigitalThreshold {
+    /// Press and release levels in raw units.
+    pub fn to_raw(self) -> [u8; 2] {
+        let level = |value: f64| to_raw(value.clamp(0.0, 1.0)).max(1);
+        let press = level(self.press);
+        let release = self.release.map_or(press, level).min(press);
+        [press, release]
+    }
 }
 
 impl Default for TriggerOptions {
     fn default() -> Self {
         Self {
+            mode: TriggerMode::Analog,
+            hair_travel: 0.05,
             deadzone: 0.0,
+            outer_deadzone: 0.0,
             rescale: true,
+            curve: Curve::Linear,
+            anti_deadzone: 0.0,
+            jitter: 0.0,
+            smoothing: 0,
+            smoothing_time: None,
+            smoothing_mode: SmoothingMode::Average,
+            one_euro: OneEuroOptions::default(),
+            digital_threshold: DigitalThreshold::default(),
         }
     }
 }
 
 impl TriggerOptions {
+    /// Checks that the deadzones leave some travel and that the curve and smoothing are valid.
+    pub fn validate(&self) -> Result<()> {
+        let (deadzone, outer_deadzone) = (self.deadzone, self.outer_deadzone);
+        if !(0.0..1.0).contains(&deadzone) || !(0.0..1.0).contains(&outer_deadzone) {
+            bail!(
+                "Trigger deadzones must be at least 0 and less than 1! (got {deadzone} and {outer_deadzone})"
+            );
+        }
+        // Overlapping deadzones would turn the trigger into a button
+        if deadzone + outer_deadzone >= 1.0 {
+            bail!(
+                "Trigger deadzones must leave some of the travel! (got {deadzone} and {outer_deadzone})"
+            );
+        }
+        self.curve.validate()?;
+        self.one_euro.validate()?;
+        validate_time(self.smoothing_mode, self.smoothing_time)
+    }
+
     pub fn gen_lut(&self) -> Vec<u8> {
         gen_lut(self)
     }
+
+    pub fn smoothing_params(&self) -> SmoothingParams {
+        SmoothingParams::new(
+            self.smoothing_mode,
+            self.smoothing,
+            self.smoothing_time,
+            &self.one_euro,
+        )
+    }
+
+    /// Hair trigger travel in raw units. (0 if not in hair mode)
+    pub fn hair_travel(&self) -> u8 {
+        match self.mode {
+            TriggerMode::Hair => to_raw(self.hair_travel.clamp(0.0, 1.0)).max(1),
+            _ => 0,
+        }
+    }
+
+    /// Jitter threshold in raw units.
+    pub fn jitter_threshold(&self) -> u8 {
+        to_raw(self.jitter.clamp(0.0, 1.0))
+    }
 }
 
 //
@@ -39,7 +147,19 @@ fn gen_lut(options: &TriggerOptions) -> Vec<u8> {
     for index in 0..=u8::MAX {
         let mut value = to_scaled(index);
 
-        apply_deadzone(&mut value, options);
+        let mut live = value;
+        apply_deadzone(&mut live, options, true);
+        apply_deadzone(&mut value, options, options.rescale);
+        value = options.curve.apply(value);
+        value = match options.rescale {
+            true => anti_deadzone_scale(value, options.anti_deadzone),
+            // Start the anti-deadzone from the deadzone edge even without rescaling
+            false => anti_deadzone_floor(value, options.curve.apply(live), options.anti_deadzone),
+        };
+
+        if options.mode == TriggerMode::Digital && value > 0.0 {
+            value = 1.0;
+        }
 
         values[index as usize] = to_raw(value);
     }
@@ -47,11 +167,15 @@ fn gen_lut(options: &TriggerOptions) -> Vec<u8> {
     values
 }
 
-fn apply_deadzone(input: &mut f64, options: &TriggerOptions) {
+fn apply_deadzone(input: &mut f64, options: &TriggerOptions, rescale: bool) {
+    let saturation = 1.0 - options.outer_deadzone;
     if *input < options.deadzone {
         *input = 0.0;
-    } else if options.rescale {
-        *input = deadzone_scale(*input, options.deadzone);
+    } else if *input >= saturation {
+        *input = 1.0;
+    } else if rescale {
+        *input =
+            deadzone_scale(*input, options.deadzone) / deadzone_scale(saturation, options.deadzone);
     }
 }
 
@@ -79,4 +203,175 @@ mod tests {
             assert_eq!(v, to_raw(to_scaled(v)));
         }
     }
+
+    fn assert_monotonic(options: TriggerOptions) {
+        let lut = options.gen_lut();
+        assert_eq!(0, lut[0], "{options:?}");
+        assert_eq!(u8::MAX, lut[255], "{options:?}");
+        assert!(lut.windows(2).all(|w| w[0] <= w[1]), "{options:?}");
+    }
+
+    #[test]
+    fn check_monotonic() {
+        let curves = [
+            Curve::Linear,
+            Curve::Power(0.5),
+            Curve::Power(3.0),
+            Curve::Points(vec![[0.2, 0.5], [0.6, 0.7]]),
+            Curve::Bezier([0.7, 0.0, 0.3, 1.0]),
+        ];
+
+        for curve in curves {
+            for (deadzone, outer_deadzone) in [(0.0, 0.0), (0.1, 0.0), (0.0, 0.2), (0.3, 0.3)] {
+                for rescale in [true, false] {
+                    assert_monotonic(TriggerOptions {
+                        deadzone,
+                        outer_deadzone,
+                        rescale,
+                        curve: curve.clone(),
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn check_outer_deadzone() {
+        let lut = TriggerOptions {
+            outer_deadzone: 0.2,
+            ..Default::default()
+        }
+        .gen_lut();
+
+        assert_eq!(128, lut[102]);
+        assert!(lut[203] < u8::MAX);
+        assert!(lut[204..].iter().all(|&v| v == u8::MAX));
+    }
+
+    #[test]
+    fn check_anti_deadzone() {
+        let lut = TriggerOptions {
+            deadzone: 0.1,
+            anti_deadzone: 0.25,
+            ..Default::default()
+        }
+        .gen_lut();
+
+        assert!(lut[..26].iter().all(|&v| v == 0));
+        assert_eq!(64, lut[26]);
+        assert_eq!(u8::MAX, lut[255]);
+        assert!(lut.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn check_anti_deadzone_unscaled() {
+        let lut = TriggerOptions {
+            deadzone: 0.1,
+            rescale: false,
+            anti_deadzone: 0.25,
+            ..Default::default()
+        }
+        .gen_lut();
+
+        // Starts from the anti-deadzone instead of the deadzone edge
+        assert!(lut[..26].iter().all(|&v| v == 0));
+        assert_eq!(64, lut[26]);
+        assert_eq!(u8::MAX, lut[255]);
+        assert!(lut.windows(2).all(|w| w[0] <= w[1]));
+
+        // Anti-deadzone smaller than the deadzone keeps the unscaled input
+        let lut = TriggerOptions {
+            deadzone: 0.1,
+            rescale: false,
+            anti_deadzone: 0.05,
+            ..Default::default()
+        }
+        .gen_lut();
+        assert_eq!(26, lut[26]);
+        assert_eq!(128, lut[128]);
+    }
+
+    #[test]
+    fn check_jitter_threshold() {
+        let threshold = |jitter| {
+            TriggerOptions {
+                jitter,
+                ..Default::default()
+            }
+            .jitter_threshold()
+        };
+
+        assert_eq!(0, threshold(0.0));
+        assert_eq!(5, threshold(0.02));
+        assert_eq!(0, threshold(-1.0));
+        assert_eq!(u8::MAX, threshold(2.0));
+    }
+
+    #[test]
+    fn check_digital_threshold() {
+        let threshold = |press, release| DigitalThreshold { press, release }.to_raw();
+
+        // Any non-zero value is pressed by default
+        assert_eq!([1, 1], DigitalThreshold::default().to_raw());
+        assert_eq!([51, 51], threshold(0.2, None));
+        assert_eq!([51, 26], threshold(0.2, Some(0.1)));
+        // Release can't be above press
+        assert_eq!([26, 26], threshold(0.1, Some(0.2)));
+        assert_eq!([255, 1], threshold(1.5, Some(-1.0)));
+    }
+
+    #[test]
+    fn check_digital_mode() {
+        let lut = TriggerOptions {
+            mode: TriggerMode::Digital,
+            deadzone: 0.1,
+            ..Default::default()
+        }
+        .gen_lut();
+
+        assert!(lut[..26].iter().all(|&v| v == 0));
+        assert!(lut[26..].iter().all(|&v| v == u8::MAX));
+    }
+
+    #[test]
+    fn check_hair_travel() {
+        let travel = |mode, hair_travel| {
+            TriggerOptions {
+                mode,
+                hair_travel,
+                ..Default::default()
+            }
+            .hair_travel()
+        };
+
+        assert_eq!(0, travel(TriggerMode::Analog, 0.1));
+        assert_eq!(0, travel(TriggerMode::Digital, 0.1));
+        assert_eq!(26, travel(TriggerMode::Hair, 0.1));
+        assert_eq!(1, travel(TriggerMode::Hair, 0.0));
+    }
+
+    #[test]
+    fn check_overlapping_deadzones() {
+        let lut = TriggerOptions {
+            deadzone: 0.6,
+            outer_deadzone: 0.6,
+            ..Default::default()
+        }
+        .gen_lut();
+
+        assert!(lut[..153].iter().all(|&v| v == 0));
+        assert!(lut[153..].iter().all(|&v| v == u8::MAX));
+    }
+
+    #[test]
+    fn check_rejected() {
+        let parse = |toml: &str| toml::from_str::<TriggerOptions>(toml).unwrap().validate();
+        assert!(parse("deadzone = 0.5\nouter_deadzone = 0.49").is_ok());
+        assert!(parse("deadzone = -0.1").is_err());
+        assert!(parse("deadzone = 1.0").is_err());
+        assert!(parse("outer_deadzone = 1.5").is_err());
+        assert!(parse("outer_deadzone = nan").is_err());
+        assert!(parse("deadzone = 0.6\nouter_deadzone = 0.4").is_err());
+    }
 }
@@ -9,6 +9,39 @@ pub const fn deadzone_scale(len: f64, deadzone: f64) -> f64 {
     (len - deadzone).max(0.0) * (1.0 / (1.0 - deadzone))
 }
 
+/// Scale non-zero length from 0.0 to 1.0+ range to start from the anti-deadzone
+pub const fn anti_deadzone_scale(len: f64, anti_deadzone: f64) -> f64 {
+    if len > 0.0 {
+        anti_deadzone + len * (1.0 - anti_deadzone)
+    } else {
+        0.0
+    }
+}
+
+/// Raise non-zero length that wasn't rescaled after the deadzone to start from the anti-deadzone,
+/// following the length with the deadzone removed from the deadzone edge
+pub fn anti_deadzone_floor(len: f64, live: f64, anti_deadzone: f64) -> f64 {
+    if len > 0.0 {
+        len.max(anti_deadzone + live * (1.0 - anti_deadzone))
+    } else {
+        0.0
+    }
+}
+
+/// Packs 32-bit fields into native endian bytes. (Panics if the fields don't fill the bytes exactly)
+pub fn fields_to_ne_bytes<const N: usize>(fields: &[u32]) -> [u8; N] {
+    assert_eq!(
+        N,
+        fields.len() * 4,
+        "Fields don't match the size of the bytes!"
+    );
+    let mut bytes = [0; N];
+    for (chunk, field) in bytes.chunks_exact_mut(4).zip(fields) {
+        chunk.copy_from_slice(&field.to_ne_bytes());
+    }
+    bytes
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -21,4 +54,38 @@ mod tests {
         assert_eq!(0.375, deadzone_scale(0.5, DEADZONE));
         assert_eq!(1.000, deadzone_scale(1.0, DEADZONE));
     }
+
+    #[test]
+    fn check_anti_deadzone_scale() {
+        const ANTI_DEADZONE: f64 = 0.5;
+        assert_eq!(0.00, anti_deadzone_scale(0.0, ANTI_DEADZONE));
+        assert_eq!(0.50, anti_deadzone_scale(f64::MIN_POSITIVE, ANTI_DEADZONE));
+        assert_eq!(0.75, anti_deadzone_scale(0.5, ANTI_DEADZONE));
+        assert_eq!(1.00, anti_deadzone_scale(1.0, ANTI_DEADZONE));
+    }
+
+    #[test]
+    fn check_anti_deadzone_floor() {
+        const ANTI_DEADZONE: f64 = 0.5;
+        assert_eq!(0.00, anti_deadzone_floor(0.0, 0.0, ANTI_DEADZONE));
+        // Starts from the anti-deadzone at the deadzone edge
+        assert_eq!(0.50, anti_deadzone_floor(0.2, 0.0, ANTI_DEADZONE));
+        assert_eq!(0.75, anti_deadzone_floor(0.6, 0.5, ANTI_DEADZONE));
+        assert_eq!(1.00, anti_deadzone_floor(1.0, 1.0, ANTI_DEADZONE));
+        // Lengths already past the anti-deadzone are kept
+        assert_eq!(0.90, anti_deadzone_floor(0.9, 0.0, 0.2));
+    }
+
+    #[test]
+    fn check_fields_to_ne_bytes() {
+        let bytes: [u8; 8] = fields_to_ne_bytes(&[1, u32::MAX]);
+        assert_eq!(1u32.to_ne_bytes(), bytes[0..4]);
+        assert_eq!(u32::MAX.to_ne_bytes(), bytes[4..8]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn check_fields_to_ne_bytes_size() {
+        let _: [u8; 8] = fields_to_ne_bytes(&[1, 2, 3]);
+    }
 }
@@ -1,4 +1,5 @@
 mod bpf;
+mod calibrate;
 mod cli;
 mod conf;
 mod device;
@@ -22,6 +23,11 @@ fn main() {
 
     match cli.command {
         Commands::Start { config } => start(config),
+        Commands::CalibrateGyro {
+            config,
+            duration,
+            write,
+        } => calibrate_gyro(config, duration, write),
     }
 }
 
@@ -42,6 +48,19 @@ fn start(config_path: PathBuf) {
     }
 }
 
+fn calibrate_gyro(config_path: PathBuf, duration: f64, write: bool) {
+    // The running service would modify the reports and could be started meanwhile
+    let instance = SingleInstance::new();
+    if !instance.single() {
+        log::error!("Stop {NAME} before calibrating!");
+        return;
+    }
+
+    if let Err(error) = calibrate::gyro(&config_path, duration, write) {
+        log::error!("Gyro calibration failed: {error}");
+    }
+}
+
 fn init_logger(options: &Cli) -> Result<()> {
     let level = match options.verbose {
         true => LevelFilter::Trace,
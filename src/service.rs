@@ -11,7 +11,8 @@ pub enum Event {
     ConfigChanged,
 }
 
-struct BpfStore(HashMap<String, Link>);
+/// Loaded eBPF programs and the unique IDs of their controllers keyed by the sysnames.
+struct BpfStore(HashMap<String, (Link, Option<String>)>);
 
 impl BpfStore {
     pub fn new() -> Self {
@@ -26,11 +27,16 @@ impl BpfStore {
         self.0.keys().map(|k| k.to_owned()).collect()
     }
 
-    pub fn load(&mut self, sysname: String, config: &Config) {
-        match crate::bpf::load(&sysname, config) {
+    pub fn uniq(&self, sysname: &String) -> Option<String> {
+        self.0.get(sysname).and_then(|(_, uniq)| uniq.clone())
+    }
+
+    pub fn load(&mut self, sysname: String, uniq: Option<String>, config: &Config) {
+        let calibration = config.calibration(uniq.as_deref());
+        match crate::bpf::load(&sysname, config, &calibration) {
             Ok(link) => {
                 log::debug!("Loaded eBPF program for {sysname}");
-                self.0.insert(sysname, link);
+                self.0.insert(sysname, (link, uniq));
             }
             Err(error) => {
                 log::error!("Failed to load eBPF program for {sysname} ({error})");
@@ -56,8 +62,12 @@ pub fn start(config_path: PathBuf) -> Result<()> {
         match main_rx.recv()? {
             Event::DeviceAdded(sysname) => {
                 if !bpf_store.contains(&sysname) {
-                    log::info!("DualSense controller connected: {sysname}");
-                    bpf_store.load(sysname, &config.config());
+                    let uniq = crate::device::uniq(&sysname);
+                    log::info!(
+                        "DualSense controller connected: {sysname} (ID: {})",
+                        uniq.as_deref().unwrap_or("unknown")
+                    );
+                    bpf_store.load(sysname, uniq, &config.config());
                 } else {
                     // Probably can only be caused by a race condition between
                     // the start of the device monitor and the manual query
@@ -73,8 +83,9 @@ pub fn start(config_path: PathBuf) -> Result<()> {
             Event::ConfigChanged => {
                 log::info!("Configuration changed. Reloading.");
                 for sysname in bpf_store.keys() {
+                    let uniq = bpf_store.uniq(&sysname);
                     bpf_store.unload(&sysname);
-                    bpf_store.load(sysname, &config.config());
+                    bpf_store.load(sysname, uniq, &config.config());
                 }
             }
         }